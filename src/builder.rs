@@ -0,0 +1,632 @@
+use crate::{
+    Channel, Clause, FilterAttrSet, FilterLicense, FilterMaintainer, FilterPlatform, MatchFlake,
+    MatchName, MatchOptionName, MatchOptionSearch, MatchProgram, MatchQueryString, MatchSearch,
+    MatchVersion, NixSearchError, OptionQuery, Query, SearchWithin, VersionReq,
+};
+
+/// Builds a [`Query`] one filter at a time.
+///
+/// Nothing is checked until [`QueryBuilder::build`] is called,
+/// so the filters can be chained in any order.
+#[derive(Debug, Clone, Default)]
+pub struct QueryBuilder {
+    max_results: Option<u32>,
+    offset: Option<u32>,
+    page: Option<u32>,
+    search_within: Option<SearchWithin>,
+
+    search: Option<String>,
+    program: Option<String>,
+    name: Option<String>,
+    version: Option<String>,
+    query_string: Option<String>,
+    version_reqs: Vec<String>,
+    clauses: Vec<Clause>,
+    filters: Vec<Clause>,
+    exclusions: Vec<Clause>,
+}
+
+impl QueryBuilder {
+    /// the amount of results returned when [`QueryBuilder::limit`] is never called.
+    pub const DEFAULT_MAX_RESULTS: u32 = 50;
+
+    /// elastic search refuses to page past this many results
+    /// (the default `index.max_result_window`).
+    pub const MAX_RESULTS_LIMIT: u32 = 10_000;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// search within a channel, like `23.11` or `nixos-23.11`
+    pub fn channel(mut self, channel: impl Into<String>) -> Self {
+        self.search_within = Some(SearchWithin::Channel(channel.into()));
+        self
+    }
+
+    /// search within flakes instead of a channel
+    pub fn flakes(mut self) -> Self {
+        self.search_within = Some(SearchWithin::Flakes);
+        self
+    }
+
+    pub fn search_within(mut self, search_within: SearchWithin) -> Self {
+        self.search_within = Some(search_within);
+        self
+    }
+
+    /// the maximum number of packages to return
+    pub fn limit(mut self, max_results: u32) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    /// skip the first `offset` results, for offset pagination.
+    ///
+    /// For walking every result, [`Query::iter`] is usually what you want.
+    pub fn offset(mut self, offset: u32) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Skip to the `page`th (zero-indexed) page of [`QueryBuilder::limit`] results.
+    ///
+    /// The offset is worked out by [`QueryBuilder::build`], so the limit can
+    /// be set before or after. It can't be combined with [`QueryBuilder::offset`].
+    pub fn page(mut self, page: u32) -> Self {
+        self.page = Some(page);
+        self
+    }
+
+    /// see [`MatchSearch`]
+    pub fn search(mut self, search: impl Into<String>) -> Self {
+        self.search = Some(search.into());
+        self
+    }
+
+    /// see [`MatchProgram`]
+    pub fn program(mut self, program: impl Into<String>) -> Self {
+        self.program = Some(program.into());
+        self
+    }
+
+    /// see [`MatchName`]
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// see [`MatchVersion`]
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    /// Only versions meeting a requirement like `>=1.70, <2`, see [`VersionReq`].
+    ///
+    /// The requirement is parsed by [`QueryBuilder::build`]. What elastic search can't
+    /// check is checked on the hits, so a page can come back with fewer than
+    /// [`QueryBuilder::limit`] packages, and the total hit count is from before that.
+    pub fn version_req(mut self, req: impl Into<String>) -> Self {
+        self.version_reqs.push(req.into());
+        self
+    }
+
+    /// see [`MatchQueryString`]
+    pub fn query_string(mut self, query_string: impl Into<String>) -> Self {
+        self.query_string = Some(query_string.into());
+        self
+    }
+
+    /// add a clause that has to match, see [`Clause`]
+    pub fn clause(mut self, clause: impl Into<Clause>) -> Self {
+        self.clauses.push(clause.into());
+        self
+    }
+
+    /// add a clause that must *not* match, like
+    /// `FilterAttrSet("haskellPackages".to_owned())`
+    pub fn exclude(mut self, clause: impl Into<Clause>) -> Self {
+        self.exclusions.push(clause.into());
+        self
+    }
+
+    /// add a clause that has to match, without affecting the score
+    pub fn filter(mut self, clause: impl Into<Clause>) -> Self {
+        self.filters.push(clause.into());
+        self
+    }
+
+    /// see [`FilterPlatform`]
+    pub fn platform(self, platform: impl Into<String>) -> Self {
+        self.filter(FilterPlatform(platform.into()))
+    }
+
+    /// see [`FilterLicense`]
+    pub fn license(self, license: impl Into<String>) -> Self {
+        self.filter(FilterLicense(license.into()))
+    }
+
+    /// only packages from one flake, see [`MatchFlake`]
+    pub fn flake(self, name: impl Into<String>) -> Self {
+        self.filter(MatchFlake { name: name.into() })
+    }
+
+    /// see [`FilterAttrSet`]
+    pub fn attr_set(self, attr_set: impl Into<String>) -> Self {
+        self.filter(FilterAttrSet(attr_set.into()))
+    }
+
+    /// see [`FilterMaintainer`]
+    pub fn maintainer(self, maintainer: impl Into<String>) -> Self {
+        self.filter(FilterMaintainer(maintainer.into()))
+    }
+
+    /// Check the input and produce the [`Query`].
+    pub fn build(self) -> Result<Query, NixSearchError> {
+        let search_within = validate_search_within(self.search_within)?;
+        let offset = match (self.offset, self.page) {
+            (Some(_), Some(_)) => return Err(invalid("set either an offset or a page, not both")),
+            (Some(offset), None) => offset,
+            (None, Some(page)) => page.saturating_mul(
+                self.max_results
+                    .unwrap_or(QueryBuilder::DEFAULT_MAX_RESULTS),
+            ),
+            (None, None) => 0,
+        };
+        let max_results = validate_window(self.max_results, offset)?;
+
+        let mut filters = self.filters;
+        for req in self.version_reqs {
+            filters.push(req.parse::<VersionReq>()?.into());
+        }
+        validate_version_reqs(&self.clauses, &filters, &self.exclusions)?;
+
+        Ok(Query {
+            max_results,
+            offset,
+            search_within,
+            search: non_empty("search", self.search)?.map(|search| MatchSearch { search }),
+            program: non_empty("program", self.program)?.map(|program| MatchProgram { program }),
+            name: non_empty("name", self.name)?.map(|name| MatchName { name }),
+            version: non_empty("version", self.version)?.map(|version| MatchVersion { version }),
+            query_string: non_empty("query_string", self.query_string)?
+                .map(|query_string| MatchQueryString { query_string }),
+            clauses: self.clauses,
+            filters,
+            exclusions: self.exclusions,
+        })
+    }
+}
+
+/// Builds an [`OptionQuery`], the options counterpart of [`QueryBuilder`].
+#[derive(Debug, Clone, Default)]
+pub struct OptionQueryBuilder {
+    max_results: Option<u32>,
+    offset: u32,
+    search_within: Option<SearchWithin>,
+
+    search: Option<String>,
+    name: Option<String>,
+}
+
+impl OptionQueryBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// search within a channel, like `23.11` or `nixos-23.11`
+    pub fn channel(mut self, channel: impl Into<String>) -> Self {
+        self.search_within = Some(SearchWithin::Channel(channel.into()));
+        self
+    }
+
+    /// search within flakes instead of a channel
+    pub fn flakes(mut self) -> Self {
+        self.search_within = Some(SearchWithin::Flakes);
+        self
+    }
+
+    pub fn search_within(mut self, search_within: SearchWithin) -> Self {
+        self.search_within = Some(search_within);
+        self
+    }
+
+    /// the maximum number of options to return
+    pub fn limit(mut self, max_results: u32) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    /// skip the first `offset` results, for offset pagination.
+    pub fn offset(mut self, offset: u32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// see [`MatchOptionSearch`]
+    pub fn search(mut self, search: impl Into<String>) -> Self {
+        self.search = Some(search.into());
+        self
+    }
+
+    /// see [`MatchOptionName`]
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Check the input and produce the [`OptionQuery`].
+    pub fn build(self) -> Result<OptionQuery, NixSearchError> {
+        let search_within = validate_search_within(self.search_within)?;
+        let max_results = validate_window(self.max_results, self.offset)?;
+
+        Ok(OptionQuery {
+            max_results,
+            offset: self.offset,
+            search_within,
+            search: non_empty("search", self.search)?.map(|search| MatchOptionSearch { search }),
+            name: non_empty("name", self.name)?.map(|name| MatchOptionName { name }),
+        })
+    }
+}
+
+fn validate_search_within(
+    search_within: Option<SearchWithin>,
+) -> Result<SearchWithin, NixSearchError> {
+    match search_within {
+        Some(SearchWithin::Channel(channel)) => {
+            Ok(SearchWithin::Channel(validate_channel(channel)?))
+        }
+        Some(SearchWithin::Flakes) => Ok(SearchWithin::Flakes),
+        None => Err(invalid(
+            "no channel selected; call `.channel(..)` or `.flakes()`",
+        )),
+    }
+}
+
+/// A [`VersionReq`] can only be checked on the hits where every hit has to meet it,
+/// anywhere else elastic search's loose narrowing would be all there is.
+fn validate_version_reqs(
+    clauses: &[Clause],
+    filters: &[Clause],
+    exclusions: &[Clause],
+) -> Result<(), NixSearchError> {
+    let unchecked = clauses
+        .iter()
+        .chain(filters)
+        .find_map(Clause::unchecked_version_req);
+    if let Some(req) = unchecked {
+        return Err(invalid(format!(
+            "the version requirement `{req}` can't be under a `should` or `must_not`"
+        )));
+    }
+
+    let excluded = exclusions.iter().find_map(Clause::any_version_req);
+    if let Some(req) = excluded {
+        return Err(invalid(format!(
+            "the version requirement `{req}` can't be excluded, use the opposite requirement"
+        )));
+    }
+    Ok(())
+}
+
+/// check the limit and offset against the result window, returning the limit
+fn validate_window(max_results: Option<u32>, offset: u32) -> Result<u32, NixSearchError> {
+    let max_results = max_results.unwrap_or(QueryBuilder::DEFAULT_MAX_RESULTS);
+    if max_results == 0 {
+        return Err(invalid("the limit must be at least 1"));
+    }
+    if max_results > QueryBuilder::MAX_RESULTS_LIMIT {
+        return Err(invalid(format!(
+            "the limit must be at most {}, got {max_results}",
+            QueryBuilder::MAX_RESULTS_LIMIT
+        )));
+    }
+    if offset.saturating_add(max_results) > QueryBuilder::MAX_RESULTS_LIMIT {
+        return Err(invalid(format!(
+            "offset + limit must be at most {}, got {}; use `Query::iter` to walk deeper",
+            QueryBuilder::MAX_RESULTS_LIMIT,
+            offset.saturating_add(max_results)
+        )));
+    }
+    Ok(max_results)
+}
+
+fn invalid(reason: impl Into<String>) -> NixSearchError {
+    NixSearchError::InvalidQuery {
+        reason: reason.into(),
+    }
+}
+
+/// check the channel, and shorten it to what the index name holds, `nixos-23.11` is `23.11`
+fn validate_channel(channel: String) -> Result<String, NixSearchError> {
+    if let Ok(channel) = channel.parse::<Channel>() {
+        return Ok(channel.to_string());
+    }
+
+    let channel = channel
+        .strip_prefix("nixos-")
+        .map(str::to_owned)
+        .unwrap_or(channel);
+    if channel.trim().is_empty() {
+        return Err(invalid("the channel must not be empty"));
+    }
+    // it goes into the url as is, so nothing that would need escaping
+    if !channel
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    {
+        return Err(invalid(format!(
+            "the channel may only contain letters, digits, `.`, `_` and `-`, got `{channel}`"
+        )));
+    }
+    Ok(channel)
+}
+
+fn non_empty(field: &str, value: Option<String>) -> Result<Option<String>, NixSearchError> {
+    match value {
+        Some(value) if value.trim().is_empty() => {
+            Err(invalid(format!("`{field}` must not be empty")))
+        }
+        value => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builder_matches_struct_payload() {
+        let by_hand = Query {
+            max_results: 20,
+            offset: 0,
+            search_within: SearchWithin::Channel("23.11".to_owned()),
+            search: Some(MatchSearch {
+                search: "rust analyzer".to_owned(),
+            }),
+            program: Some(MatchProgram {
+                program: "cargo".to_owned(),
+            }),
+            name: Some(MatchName {
+                name: "rust".to_owned(),
+            }),
+            version: None,
+            query_string: None,
+            clauses: Vec::new(),
+            filters: Vec::new(),
+            exclusions: Vec::new(),
+        };
+
+        let built = Query::builder()
+            .channel("23.11")
+            .search("rust analyzer")
+            .program("cargo")
+            .name("rust")
+            .limit(20)
+            .build()
+            .unwrap();
+
+        assert_eq!(built.payload(), by_hand.payload());
+        assert_eq!(built.get_url().unwrap(), by_hand.get_url().unwrap());
+    }
+
+    #[test]
+    fn test_builder_clauses() {
+        let query = Query::builder()
+            .channel("23.11")
+            .clause(Clause::should([
+                MatchProgram {
+                    program: "python".to_owned(),
+                },
+                MatchProgram {
+                    program: "python3".to_owned(),
+                },
+            ]))
+            .exclude(MatchName {
+                name: "python3Full".to_owned(),
+            })
+            .build()
+            .unwrap();
+
+        let must = query.payload()["query"]["bool"]["must"].clone();
+        let must = must.as_array().unwrap();
+        assert_eq!(must.len(), 2);
+        assert_eq!(must[1], query.clauses[0].to_json());
+        assert_eq!(
+            query.payload()["query"]["bool"]["must_not"][0],
+            query.exclusions[0].to_json()
+        );
+    }
+
+    #[test]
+    fn test_builder_filters() {
+        let query = Query::builder()
+            .channel("23.11")
+            .name("rust")
+            .platform("aarch64-linux")
+            .exclude(FilterAttrSet("haskellPackages".to_owned()))
+            .build()
+            .unwrap();
+
+        let bool_query = &query.payload()["query"]["bool"];
+        assert_eq!(
+            bool_query["filter"],
+            serde_json::json!([{ "term": { "package_platforms": "aarch64-linux" } }])
+        );
+        assert_eq!(
+            bool_query["must_not"],
+            serde_json::json!([{ "term": { "package_attr_set": "haskellPackages" } }])
+        );
+
+        // without filters the payload doesn't mention them at all
+        let plain = Query::builder()
+            .channel("23.11")
+            .name("rust")
+            .build()
+            .unwrap();
+        assert!(plain.payload()["query"]["bool"].get("filter").is_none());
+        assert!(plain.payload()["query"]["bool"].get("must_not").is_none());
+    }
+
+    #[test]
+    fn test_builder_flake() {
+        let query = Query::builder()
+            .flakes()
+            .search("formatter")
+            .flake("treefmt")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            query.payload()["query"]["bool"]["filter"],
+            serde_json::json!([{ "match_phrase": { "flake_name": "treefmt" } }])
+        );
+    }
+
+    #[test]
+    fn test_builder_version_req() {
+        let query = Query::builder()
+            .channel("23.11")
+            .name("python")
+            .version_req("=3.11.*")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            query.payload()["query"]["bool"]["filter"],
+            serde_json::json!([{
+                "bool": { "filter": [{ "prefix": { "package_pversion": "3.11" } }] }
+            }])
+        );
+
+        assert!(matches!(
+            Query::builder().channel("23.11").version_req(">=").build(),
+            Err(NixSearchError::InvalidVersionReq { .. })
+        ));
+    }
+
+    #[test]
+    fn test_builder_version_req_placement() {
+        let req = || "<2".parse::<VersionReq>().unwrap();
+        let name = || MatchName {
+            name: "rust".to_owned(),
+        };
+
+        let nested = Query::builder()
+            .channel("23.11")
+            .clause(Clause::must([
+                Clause::from(name()),
+                Clause::filter([req()]),
+            ]))
+            .build()
+            .unwrap();
+        assert_eq!(nested.clauses[0].required_versions(), [&req()]);
+
+        let bad = [
+            Query::builder().channel("23.11").exclude(req()),
+            Query::builder()
+                .channel("23.11")
+                .exclude(Clause::must([req()])),
+            Query::builder()
+                .channel("23.11")
+                .clause(Clause::should([Clause::from(name()), Clause::from(req())])),
+            Query::builder()
+                .channel("23.11")
+                .filter(Clause::must([Clause::must_not([req()])])),
+        ];
+        for builder in bad {
+            assert!(matches!(
+                builder.build(),
+                Err(NixSearchError::InvalidQuery { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_builder_defaults() {
+        let query = Query::builder().flakes().build().unwrap();
+        assert_eq!(query.max_results, QueryBuilder::DEFAULT_MAX_RESULTS);
+        assert_eq!(query.search_within, SearchWithin::Flakes);
+        assert_eq!(query.offset, 0);
+    }
+
+    #[test]
+    fn test_builder_channel_prefix() {
+        for channel in ["nixos-23.11", "23.11"] {
+            let query = Query::builder().channel(channel).build().unwrap();
+            assert_eq!(
+                query.search_within,
+                SearchWithin::Channel("23.11".to_owned())
+            );
+        }
+
+        let query = Query::builder()
+            .search_within(Channel::Unstable.into())
+            .build()
+            .unwrap();
+        assert_eq!(
+            query.search_within,
+            SearchWithin::Channel("unstable".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_builder_page() {
+        let query = Query::builder()
+            .channel("23.11")
+            .limit(20)
+            .page(3)
+            .build()
+            .unwrap();
+        assert_eq!(query.offset, 60);
+        assert_eq!(query.payload()["from"], 60);
+        assert_eq!(query.payload()["size"], 20);
+
+        let page_first = Query::builder()
+            .page(3)
+            .limit(20)
+            .channel("23.11")
+            .build()
+            .unwrap();
+        assert_eq!(page_first.payload(), query.payload());
+
+        let default_limit = Query::builder().channel("23.11").page(2).build().unwrap();
+        assert_eq!(default_limit.offset, 2 * QueryBuilder::DEFAULT_MAX_RESULTS);
+    }
+
+    #[test]
+    fn test_builder_rejects_bad_input() {
+        let bad = [
+            Query::builder().name("rust"),
+            Query::builder().channel("nixos-"),
+            Query::builder().channel(""),
+            Query::builder().channel("23 11"),
+            Query::builder().channel("23.11/../x"),
+            Query::builder().channel("23.11?pretty"),
+            Query::builder().channel("23.11#x"),
+            Query::builder().channel("23%2E11"),
+            Query::builder().channel("23.11").limit(0),
+            Query::builder()
+                .channel("23.11")
+                .limit(QueryBuilder::MAX_RESULTS_LIMIT + 1),
+            Query::builder().channel("23.11").name("  "),
+            Query::builder()
+                .channel("23.11")
+                .offset(QueryBuilder::MAX_RESULTS_LIMIT),
+            Query::builder()
+                .channel("23.11")
+                .limit(QueryBuilder::MAX_RESULTS_LIMIT)
+                .page(1),
+            Query::builder().channel("23.11").offset(20).page(1),
+        ];
+
+        for builder in bad {
+            assert!(matches!(
+                builder.build(),
+                Err(NixSearchError::InvalidQuery { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,422 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Method, NixSearchError, Transport, TransportRequest, TransportResponse};
+
+/// Where and for how long responses are kept on disk, see [`CachingTransport`].
+///
+/// Searches and version lookups get their own time to live, the
+/// version history of a package changes a lot less often than a channel.
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use nix_elastic_search::{Cache, Client, Query};
+///
+/// let cache = Cache::from_env().search_ttl(Duration::from_secs(10 * 60));
+/// let client = Client::builder().cache(cache).build().unwrap();
+///
+/// let query = Query::builder().channel("23.11").name("rust").build().unwrap();
+/// // the second search doesn't touch the network
+/// client.send(&query).unwrap();
+/// client.send(&query).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cache {
+    dir: PathBuf,
+    search_ttl: Duration,
+    versions_ttl: Duration,
+    max_size: u64,
+    refresh: bool,
+}
+
+impl Cache {
+    pub const DEFAULT_SEARCH_TTL: Duration = Duration::from_secs(60 * 60);
+    pub const DEFAULT_VERSIONS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+    /// in bytes
+    pub const DEFAULT_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+    /// a cache in `dir`, which is created when the first response is stored
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            search_ttl: Self::DEFAULT_SEARCH_TTL,
+            versions_ttl: Self::DEFAULT_VERSIONS_TTL,
+            max_size: Self::DEFAULT_MAX_SIZE,
+            refresh: false,
+        }
+    }
+
+    /// A cache in `$XDG_CACHE_HOME/nix-elastic-search`, or
+    /// `~/.cache/nix-elastic-search` when that isn't set.
+    pub fn from_env() -> Self {
+        let cache_home = std::env::var_os("XDG_CACHE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+            .unwrap_or_else(std::env::temp_dir);
+
+        Self::new(cache_home.join("nix-elastic-search"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// how long a search (or anything else sent to elastic search) is kept
+    pub fn search_ttl(mut self, ttl: Duration) -> Self {
+        self.search_ttl = ttl;
+        self
+    }
+
+    /// how long a version lookup is kept
+    pub fn versions_ttl(mut self, ttl: Duration) -> Self {
+        self.versions_ttl = ttl;
+        self
+    }
+
+    /// the most bytes the cache may take up, the oldest responses are removed past that
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = bytes;
+        self
+    }
+
+    /// ignore what's cached and fetch everything again, still storing the new responses
+    pub fn refresh(mut self, refresh: bool) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// remove every cached response
+    pub fn clear(&self) -> Result<(), NixSearchError> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(NixSearchError::TransportError {
+                    source: Box::new(err),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn ttl(&self, request: &TransportRequest) -> Duration {
+        // elastic search endpoints all start with `_` (`_search`, `_alias`),
+        // anything else is a version lookup.
+        let elastic_search = request
+            .url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .is_some_and(|last| last.starts_with('_'));
+
+        if elastic_search {
+            self.search_ttl
+        } else {
+            self.versions_ttl
+        }
+    }
+
+    fn path(&self, request: &TransportRequest) -> PathBuf {
+        let method = match request.method {
+            Method::Get => "GET",
+            Method::Post => "POST",
+        };
+        let body = request
+            .body
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+
+        // with the credentials, so a client with other (or wrong)
+        // credentials doesn't get a response it wouldn't have gotten.
+        let authorization = request.header("Authorization").unwrap_or_default();
+
+        let key = fnv1a([method, request.url.as_str(), &body, authorization]);
+        self.dir.join(format!("{key:016x}.json"))
+    }
+
+    /// the cached response to `request`, unless refreshing
+    pub(crate) fn cached(&self, request: &TransportRequest) -> Option<TransportResponse> {
+        if self.refresh {
+            return None;
+        }
+        self.load(request)
+    }
+
+    /// store `response` when it's a success
+    pub(crate) fn keep(&self, request: &TransportRequest, response: &TransportResponse) {
+        if (200..300).contains(&response.status) {
+            self.store(request, response);
+        }
+    }
+
+    fn load(&self, request: &TransportRequest) -> Option<TransportResponse> {
+        let path = self.path(request);
+        let entry = serde_json::from_slice::<CacheEntry>(&fs::read(&path).ok()?).ok()?;
+
+        let age = now().saturating_sub(entry.stored_at);
+        // the url is compared too, in case two requests hash the same.
+        if age >= self.ttl(request).as_secs() || entry.url != request.url.as_str() {
+            let _ = fs::remove_file(path);
+            return None;
+        }
+
+        Some(TransportResponse {
+            status: entry.status,
+            body: entry.body,
+        })
+    }
+
+    fn store(&self, request: &TransportRequest, response: &TransportResponse) -> Option<()> {
+        let entry = CacheEntry {
+            url: request.url.to_string(),
+            stored_at: now(),
+            status: response.status,
+            body: response.body.clone(),
+        };
+
+        fs::create_dir_all(&self.dir).ok()?;
+        let path = self.path(request);
+        // written next to the entry and renamed over it, so
+        // another process never reads half a response.
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        let mut file = fs::File::create(&tmp).ok()?;
+        file.write_all(&serde_json::to_vec(&entry).ok()?).ok()?;
+        fs::rename(&tmp, &path).ok()?;
+
+        self.evict();
+        Some(())
+    }
+
+    /// remove the oldest entries until the cache fits in `max_size`
+    fn evict(&self) {
+        let Ok(dir) = fs::read_dir(&self.dir) else {
+            return;
+        };
+
+        let mut entries = dir
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+            })
+            .collect::<Vec<_>>();
+
+        let mut size = entries.iter().map(|(_, len, _)| len).sum::<u64>();
+        entries.sort();
+
+        for (_, len, path) in entries {
+            if size <= self.max_size {
+                break;
+            }
+            if fs::remove_file(path).is_ok() {
+                size -= len;
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    /// seconds since the unix epoch
+    stored_at: u64,
+    status: u16,
+    body: String,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// a hash that stays the same between builds, unlike `DefaultHasher`
+fn fnv1a<'a>(parts: impl IntoIterator<Item = &'a str>) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for part in parts {
+        // the separator keeps `ab` + `c` apart from `a` + `bc`
+        for byte in part.bytes().chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// A [`Transport`] that answers from a [`Cache`] when it can,
+/// and sends the request through `T` when it can't.
+///
+/// Responses are kept per `Authorization` header, so clients with different
+/// credentials don't share them.
+///
+/// Only successful responses are stored. The cache is best effort,
+/// when it can't be read or written the request is just sent.
+#[derive(Debug, Clone)]
+pub struct CachingTransport<T> {
+    cache: Cache,
+    inner: T,
+}
+
+impl<T: Transport> CachingTransport<T> {
+    pub fn new(cache: Cache, inner: T) -> Self {
+        Self { cache, inner }
+    }
+
+    pub fn cache(&self) -> &Cache {
+        &self.cache
+    }
+}
+
+impl<T: Transport> Transport for CachingTransport<T> {
+    fn send(&self, request: &TransportRequest) -> Result<TransportResponse, NixSearchError> {
+        if let Some(response) = self.cache.cached(request) {
+            return Ok(response);
+        }
+
+        let response = self.inner.send(request)?;
+        self.cache.keep(request, &response);
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{Client, MemoryTransport, Query};
+
+    fn temp_cache(name: &str) -> Cache {
+        let dir = std::env::temp_dir().join(format!(
+            "nix-elastic-search-test-{name}-{}",
+            std::process::id()
+        ));
+        let cache = Cache::new(dir);
+        cache.clear().unwrap();
+        cache
+    }
+
+    fn request(url: &str) -> TransportRequest {
+        TransportRequest {
+            method: Method::Post,
+            url: url.parse().unwrap(),
+            headers: Vec::new(),
+            body: Some(serde_json::json!({ "size": 1 })),
+        }
+    }
+
+    #[test]
+    fn test_cache_hit_and_refresh() {
+        let cache = temp_cache("hit");
+        let memory = MemoryTransport::new();
+        memory.push_response(200, "first");
+        memory.push_response(200, "second");
+
+        let request = request("https://example.org/latest-nixos-23.11/_search");
+        let transport = CachingTransport::new(cache.clone(), &memory);
+        assert_eq!(transport.send(&request).unwrap().body, "first");
+        assert_eq!(transport.send(&request).unwrap().body, "first");
+        assert_eq!(memory.requests().len(), 1);
+
+        let refreshing = CachingTransport::new(cache.clone().refresh(true), &memory);
+        assert_eq!(refreshing.send(&request).unwrap().body, "second");
+        assert_eq!(transport.send(&request).unwrap().body, "second");
+        assert_eq!(memory.requests().len(), 2);
+
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn test_cache_expiry_and_errors() {
+        let cache = temp_cache("expiry").search_ttl(Duration::ZERO);
+        let memory = MemoryTransport::new();
+        memory.push_response(200, "first");
+        memory.push_response(200, "second");
+        memory.push_response(429, "slow down");
+        memory.push_response(200, "fourth");
+
+        let search = request("https://example.org/latest-nixos-23.11/_search");
+        let transport = CachingTransport::new(cache.clone(), &memory);
+        transport.send(&search).unwrap();
+        assert_eq!(transport.send(&search).unwrap().body, "second");
+
+        // version lookups have their own, longer, ttl. errors aren't kept.
+        let versions = request("https://lazamar.co.uk/nix-versions/?package=ripgrep");
+        assert_eq!(transport.send(&versions).unwrap().status, 429);
+        assert_eq!(transport.send(&versions).unwrap().body, "fourth");
+        assert_eq!(transport.send(&versions).unwrap().body, "fourth");
+        assert_eq!(memory.requests().len(), 4);
+
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn test_cache_eviction() {
+        let cache = temp_cache("eviction").max_size(150);
+        let memory = MemoryTransport::new();
+        for _ in 0..3 {
+            memory.push_response(200, "x".repeat(50));
+        }
+
+        let transport = CachingTransport::new(cache.clone(), &memory);
+        for index in ["a", "b", "c"] {
+            transport
+                .send(&request(&format!("https://example.org/{index}/_search")))
+                .unwrap();
+        }
+
+        let size = fs::read_dir(cache.dir())
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.metadata().unwrap().len())
+            .sum::<u64>();
+        assert!(size <= 150, "cache is {size} bytes");
+        assert!(size > 0);
+
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn test_cache_is_per_credentials() {
+        let cache = temp_cache("credentials");
+        let memory = Arc::new(MemoryTransport::new());
+        memory.push_response(200, r#"{ "hits": { "hits": [] } }"#);
+        memory.push_response(401, "Unauthorized");
+
+        let client = |password: &str| {
+            Client::builder()
+                .credentials("user", password)
+                .transport(memory.clone())
+                .cache(cache.clone())
+                .build()
+                .unwrap()
+        };
+        let query = Query::builder()
+            .channel("23.11")
+            .name("rust")
+            .build()
+            .unwrap();
+
+        assert!(client("hunter2").send(&query).unwrap().is_empty());
+        assert!(matches!(
+            client("hunter3").send(&query),
+            Err(NixSearchError::AuthenticationFailed { status: 401, .. })
+        ));
+        assert_eq!(memory.requests().len(), 2);
+
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn test_cache_key_is_stable() {
+        assert_eq!(fnv1a(["a"]), 0x089b_e207_b544_f1e4);
+        assert_ne!(fnv1a(["ab", "c"]), fnv1a(["a", "bc"]));
+    }
+}
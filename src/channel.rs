@@ -0,0 +1,339 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    response::ElasticSearchResponseError, Client, Method, NixSearchError, SearchWithin, Transport,
+};
+
+/// A nixpkgs channel that search.nixos.org has an index for.
+///
+/// Parses both the short form [`SearchWithin::Channel`] takes and the full
+/// channel name:
+///
+/// ```rust
+/// use nix_elastic_search::Channel;
+///
+/// let stable: Channel = "nixos-23.11".parse().unwrap();
+/// assert_eq!(stable, Channel::Stable { year: 23, month: 11 });
+/// assert_eq!(stable, "23.11".parse().unwrap());
+/// assert_eq!(stable.to_string(), "23.11");
+/// assert_eq!(stable.full_name(), "nixos-23.11");
+///
+/// assert_eq!("unstable".parse::<Channel>().unwrap(), Channel::Unstable);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Channel {
+    /// a release like 23.11
+    Stable {
+        year: u16,
+        month: u8,
+    },
+    Unstable,
+}
+
+impl Channel {
+    /// the channel name, like `nixos-23.11`
+    pub fn full_name(&self) -> String {
+        format!("nixos-{self}")
+    }
+}
+
+impl fmt::Display for Channel {
+    /// the short form, like `23.11` or `unstable`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Channel::Stable { year, month } => write!(f, "{year:02}.{month:02}"),
+            Channel::Unstable => write!(f, "unstable"),
+        }
+    }
+}
+
+impl FromStr for Channel {
+    type Err = NixSearchError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || NixSearchError::InvalidChannel {
+            channel: s.to_owned(),
+        };
+
+        let short = s.strip_prefix("nixos-").unwrap_or(s);
+        if short == "unstable" {
+            return Ok(Channel::Unstable);
+        }
+
+        let (year, month) = short.split_once('.').ok_or_else(invalid)?;
+        let all_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+        if !all_digits(year) || month.len() != 2 || !all_digits(month) {
+            return Err(invalid());
+        }
+
+        let year = year.parse().map_err(|_| invalid())?;
+        let month = month.parse().map_err(|_| invalid())?;
+        if !(1..=12).contains(&month) {
+            return Err(invalid());
+        }
+
+        Ok(Channel::Stable { year, month })
+    }
+}
+
+/// as the short form, like `23.11`
+impl Serialize for Channel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Channel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let channel = String::deserialize(deserializer)?;
+        channel.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<Channel> for SearchWithin {
+    fn from(value: Channel) -> Self {
+        SearchWithin::Channel(value.to_string())
+    }
+}
+
+impl From<Channel> for String {
+    fn from(value: Channel) -> Self {
+        value.to_string()
+    }
+}
+
+/// what an index holds
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IndexGroup {
+    /// the packages and options of a channel
+    Channel(Channel),
+    /// a group of flakes, like `manual` (what [`SearchWithin::Flakes`] searches)
+    Flakes(String),
+}
+
+/// An index alias that exists on the cluster, see [`list_channels`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AvailableIndex {
+    pub group: IndexGroup,
+    /// the version of the search.nixos.org import schema,
+    /// the `42` in `latest-42-nixos-23.11`
+    pub schema_version: u32,
+    /// the alias, like `latest-42-nixos-23.11`
+    pub alias: String,
+    /// the concrete index behind the alias
+    pub index: String,
+}
+
+impl AvailableIndex {
+    /// parse an alias like `latest-42-nixos-23.11` or `latest-42-group-manual`
+    fn parse(alias: &str, index: &str) -> Option<Self> {
+        let (prefix, group) = if let Some((prefix, channel)) = alias.split_once("-nixos-") {
+            (prefix, IndexGroup::Channel(channel.parse().ok()?))
+        } else if let Some((prefix, group)) = alias.split_once("-group-") {
+            (prefix, IndexGroup::Flakes(group.to_owned()))
+        } else {
+            return None;
+        };
+
+        let schema_version = prefix.rsplit('-').next()?.parse().ok()?;
+
+        Some(Self {
+            group,
+            schema_version,
+            alias: alias.to_owned(),
+            index: index.to_owned(),
+        })
+    }
+}
+
+/// List the channels and flake groups the default [`Client`] can search.
+///
+/// Aliases that don't look like a channel or flake group are left out.
+///
+/// ```rust,no_run
+/// use nix_elastic_search::{list_channels, Channel, IndexGroup};
+///
+/// let newest_stable = list_channels()
+///     .unwrap()
+///     .into_iter()
+///     .filter_map(|index| match index.group {
+///         IndexGroup::Channel(channel @ Channel::Stable { .. }) => Some(channel),
+///         _ => None,
+///     })
+///     .max();
+/// ```
+pub fn list_channels() -> Result<Vec<AvailableIndex>, NixSearchError> {
+    Client::shared().list_channels()
+}
+
+impl Client {
+    /// List the channels and flake groups this cluster can search, see [`list_channels`].
+    pub fn list_channels(&self) -> Result<Vec<AvailableIndex>, NixSearchError> {
+        self.list_channels_with(&*self.transport)
+    }
+
+    pub(crate) fn list_channels_with(
+        &self,
+        transport: &dyn Transport,
+    ) -> Result<Vec<AvailableIndex>, NixSearchError> {
+        let url = self
+            .base_url()
+            .join("_alias")
+            .map_err(|err| NixSearchError::InvalidConfig {
+                reason: format!("can't build the alias url: {err}"),
+            })?;
+
+        let res = transport.send(&self.request(Method::Get, url, None))?;
+        parse_aliases(res.status, &res.body)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AliasResponse {
+    Error {
+        error: Box<ElasticSearchResponseError>,
+        status: i64,
+    },
+    Indices(BTreeMap<String, IndexAliases>),
+}
+
+#[derive(Deserialize)]
+struct IndexAliases {
+    #[serde(default)]
+    aliases: BTreeMap<String, serde_json::Value>,
+}
+
+fn parse_aliases(status: u16, text: &str) -> Result<Vec<AvailableIndex>, NixSearchError> {
+    let read = match serde_json::from_str::<AliasResponse>(text) {
+        Ok(read) => read,
+        Err(_) if status >= 400 => return Err(crate::response::status_error(status)),
+        Err(source) => {
+            return Err(NixSearchError::DeserializationError {
+                path: crate::SerdeNixPackagePath::for_response::<AliasResponse>(text.to_owned()),
+                source,
+            })
+        }
+    };
+
+    let indices = match read {
+        AliasResponse::Error { error, status } => {
+            return Err(crate::response::classify_error(*error, status))
+        }
+        // `{}` or a proxy's json, not an alias listing
+        AliasResponse::Indices(_) if status >= 400 => {
+            return Err(crate::response::status_error(status))
+        }
+        AliasResponse::Indices(indices) => indices,
+    };
+
+    let mut available = indices
+        .iter()
+        .flat_map(|(index, aliases)| {
+            aliases
+                .aliases
+                .keys()
+                .filter_map(|alias| AvailableIndex::parse(alias, index))
+        })
+        .collect::<Vec<_>>();
+    available.sort();
+
+    Ok(available)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MemoryTransport;
+
+    #[test]
+    fn test_parse_channel() {
+        assert_eq!(
+            "24.05".parse::<Channel>().unwrap(),
+            Channel::Stable { year: 24, month: 5 }
+        );
+        assert_eq!(
+            "nixos-unstable".parse::<Channel>().unwrap(),
+            Channel::Unstable
+        );
+        assert_eq!(Channel::Stable { year: 24, month: 5 }.to_string(), "24.05");
+
+        for bad in [
+            "",
+            "23.1",
+            "23.13",
+            "nixos-",
+            "23-11",
+            "23.11-small",
+            "latest",
+        ] {
+            assert!(bad.parse::<Channel>().is_err(), "{bad} should not parse");
+        }
+    }
+
+    #[test]
+    fn test_list_channels() {
+        let transport = MemoryTransport::new();
+        transport.push_response(
+            200,
+            serde_json::json!({
+                "nixos-42-23.11-7a1a6a": { "aliases": { "latest-42-nixos-23.11": {} } },
+                "nixos-42-unstable-1b2c3d": { "aliases": { "latest-42-nixos-unstable": {} } },
+                "group-42-manual-4e5f6a": { "aliases": { "latest-42-group-manual": {} } },
+                ".kibana_1": { "aliases": { ".kibana": {} } },
+                "nixos-41-23.05-000000": { "aliases": {} },
+            })
+            .to_string(),
+        );
+
+        let client = Client::default();
+        let available = client.list_channels_with(&transport).unwrap();
+
+        assert_eq!(
+            available
+                .iter()
+                .map(|index| (&index.group, index.schema_version))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    &IndexGroup::Channel(Channel::Stable {
+                        year: 23,
+                        month: 11
+                    }),
+                    42
+                ),
+                (&IndexGroup::Channel(Channel::Unstable), 42),
+                (&IndexGroup::Flakes("manual".to_owned()), 42),
+            ]
+        );
+        assert_eq!(available[0].index, "nixos-42-23.11-7a1a6a");
+
+        let request = &transport.requests()[0];
+        assert_eq!(request.method, Method::Get);
+        assert!(request.url.path().ends_with("/_alias"));
+    }
+
+    #[test]
+    fn test_list_channels_failed_status() {
+        let transport = MemoryTransport::new();
+        transport.push_response(403, r#"{"message":"Forbidden"}"#);
+        transport.push_response(503, "{}");
+        transport.push_response(502, "<html>Bad Gateway</html>");
+
+        let client = Client::default();
+        assert!(matches!(
+            client.list_channels_with(&transport),
+            Err(NixSearchError::AuthenticationFailed { status: 403, .. })
+        ));
+        assert!(matches!(
+            client.list_channels_with(&transport),
+            Err(NixSearchError::ServerError { status: 503 })
+        ));
+        assert!(matches!(
+            client.list_channels_with(&transport),
+            Err(NixSearchError::ServerError { status: 502 })
+        ));
+    }
+}
@@ -0,0 +1,281 @@
+use serde_json::json;
+
+use crate::{
+    FilterAttrName, FilterAttrSet, FilterLicense, FilterMaintainer, FilterPlatform, FilterProgram,
+    MatchFlake, MatchName, MatchProgram, MatchQueryString, MatchSearch, MatchVersion, VersionReq,
+};
+
+/// A node of an elastic search `bool` query.
+///
+/// The `Match*` and `Filter*` types are the leaves, the other variants combine them.
+/// Anything that goes into a [`Query`](crate::Query) as a clause has to match,
+/// so the combinators are what make "or" and "not" possible:
+///
+/// ```rust
+/// use nix_elastic_search::{Clause, MatchName, MatchProgram, Query};
+///
+/// // programs called python or python3, but not the ones in a *-full package
+/// let clause = Clause::must([
+///     Clause::should([
+///         MatchProgram { program: "python".to_owned() },
+///         MatchProgram { program: "python3".to_owned() },
+///     ]),
+///     Clause::must_not([MatchName { name: "python3Full".to_owned() }]),
+/// ]);
+///
+/// let query = Query::builder().channel("23.11").clause(clause).build().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Clause {
+    /// every clause has to match, and they add to the score
+    Must(Vec<Clause>),
+    /// at least one of the clauses has to match
+    Should(Vec<Clause>),
+    /// none of the clauses may match
+    MustNot(Vec<Clause>),
+    /// every clause has to match, without affecting the score
+    Filter(Vec<Clause>),
+
+    Search(MatchSearch),
+    Name(MatchName),
+    Program(MatchProgram),
+    Version(MatchVersion),
+    QueryString(MatchQueryString),
+    Platform(FilterPlatform),
+    License(FilterLicense),
+    AttrSet(FilterAttrSet),
+    AttrName(FilterAttrName),
+    Maintainer(FilterMaintainer),
+    ExactProgram(FilterProgram),
+    Flake(MatchFlake),
+    /// Only narrows what it can on the server, see [`VersionReq::to_json`], the
+    /// hits are checked after. So it can't be under a [`Clause::Should`] or
+    /// [`Clause::MustNot`], or be excluded, [`QueryBuilder::build`](crate::QueryBuilder::build)
+    /// refuses that.
+    VersionReq(VersionReq),
+    /// any elastic search query, for when nothing above fits
+    Raw(serde_json::Value),
+}
+
+impl Clause {
+    pub fn must<C: Into<Clause>>(clauses: impl IntoIterator<Item = C>) -> Self {
+        Clause::Must(clauses.into_iter().map(Into::into).collect())
+    }
+
+    pub fn should<C: Into<Clause>>(clauses: impl IntoIterator<Item = C>) -> Self {
+        Clause::Should(clauses.into_iter().map(Into::into).collect())
+    }
+
+    pub fn must_not<C: Into<Clause>>(clauses: impl IntoIterator<Item = C>) -> Self {
+        Clause::MustNot(clauses.into_iter().map(Into::into).collect())
+    }
+
+    pub fn filter<C: Into<Clause>>(clauses: impl IntoIterator<Item = C>) -> Self {
+        Clause::Filter(clauses.into_iter().map(Into::into).collect())
+    }
+
+    /// The [`VersionReq`]s every hit has to meet, the ones that are only
+    /// under [`Clause::Must`] and [`Clause::Filter`], so they can be checked on the hits.
+    pub(crate) fn required_versions(&self) -> Vec<&VersionReq> {
+        match self {
+            Clause::Must(clauses) | Clause::Filter(clauses) => {
+                clauses.iter().flat_map(Clause::required_versions).collect()
+            }
+            Clause::VersionReq(req) => vec![req],
+            _ => Vec::new(),
+        }
+    }
+
+    /// A [`VersionReq`] under a [`Clause::Should`] or [`Clause::MustNot`], which
+    /// elastic search only narrows, and which the hits can't be checked against.
+    pub(crate) fn unchecked_version_req(&self) -> Option<&VersionReq> {
+        match self {
+            Clause::Must(clauses) | Clause::Filter(clauses) => {
+                clauses.iter().find_map(Clause::unchecked_version_req)
+            }
+            Clause::Should(clauses) | Clause::MustNot(clauses) => {
+                clauses.iter().find_map(Clause::any_version_req)
+            }
+            _ => None,
+        }
+    }
+
+    /// any [`VersionReq`] in the tree
+    pub(crate) fn any_version_req(&self) -> Option<&VersionReq> {
+        match self {
+            Clause::Must(clauses)
+            | Clause::Should(clauses)
+            | Clause::MustNot(clauses)
+            | Clause::Filter(clauses) => clauses.iter().find_map(Clause::any_version_req),
+            Clause::VersionReq(req) => Some(req),
+            _ => None,
+        }
+    }
+
+    /// compile the tree down to elastic search json
+    pub fn to_json(&self) -> serde_json::Value {
+        let compile = |clauses: &[Clause]| clauses.iter().map(Clause::to_json).collect::<Vec<_>>();
+
+        match self {
+            Clause::Must(clauses) => json!({ "bool": { "must": compile(clauses) } }),
+            Clause::Should(clauses) => json!({
+                "bool": {
+                    "should": compile(clauses),
+                    // a bool query with only `should` clauses already needs one
+                    // to match, but being explicit keeps that true when nested.
+                    "minimum_should_match": 1,
+                }
+            }),
+            Clause::MustNot(clauses) => json!({ "bool": { "must_not": compile(clauses) } }),
+            Clause::Filter(clauses) => json!({ "bool": { "filter": compile(clauses) } }),
+            Clause::Search(search) => search.to_json(),
+            Clause::Name(name) => name.to_json(),
+            Clause::Program(program) => program.to_json(),
+            Clause::Version(version) => version.to_json(),
+            Clause::QueryString(query_string) => query_string.to_json(),
+            Clause::Platform(platform) => platform.to_json(),
+            Clause::License(license) => license.to_json(),
+            Clause::AttrSet(attr_set) => attr_set.to_json(),
+            Clause::AttrName(attr_name) => attr_name.to_json(),
+            Clause::Maintainer(maintainer) => maintainer.to_json(),
+            Clause::ExactProgram(program) => program.to_json(),
+            Clause::Flake(flake) => flake.to_json(),
+            Clause::VersionReq(req) => req.to_json(),
+            Clause::Raw(raw) => raw.clone(),
+        }
+    }
+}
+
+impl From<MatchSearch> for Clause {
+    fn from(value: MatchSearch) -> Self {
+        Clause::Search(value)
+    }
+}
+
+impl From<MatchName> for Clause {
+    fn from(value: MatchName) -> Self {
+        Clause::Name(value)
+    }
+}
+
+impl From<MatchProgram> for Clause {
+    fn from(value: MatchProgram) -> Self {
+        Clause::Program(value)
+    }
+}
+
+impl From<MatchVersion> for Clause {
+    fn from(value: MatchVersion) -> Self {
+        Clause::Version(value)
+    }
+}
+
+impl From<MatchQueryString> for Clause {
+    fn from(value: MatchQueryString) -> Self {
+        Clause::QueryString(value)
+    }
+}
+
+impl From<FilterPlatform> for Clause {
+    fn from(value: FilterPlatform) -> Self {
+        Clause::Platform(value)
+    }
+}
+
+impl From<FilterLicense> for Clause {
+    fn from(value: FilterLicense) -> Self {
+        Clause::License(value)
+    }
+}
+
+impl From<FilterAttrSet> for Clause {
+    fn from(value: FilterAttrSet) -> Self {
+        Clause::AttrSet(value)
+    }
+}
+
+impl From<FilterAttrName> for Clause {
+    fn from(value: FilterAttrName) -> Self {
+        Clause::AttrName(value)
+    }
+}
+
+impl From<FilterMaintainer> for Clause {
+    fn from(value: FilterMaintainer) -> Self {
+        Clause::Maintainer(value)
+    }
+}
+
+impl From<FilterProgram> for Clause {
+    fn from(value: FilterProgram) -> Self {
+        Clause::ExactProgram(value)
+    }
+}
+
+impl From<MatchFlake> for Clause {
+    fn from(value: MatchFlake) -> Self {
+        Clause::Flake(value)
+    }
+}
+
+impl From<VersionReq> for Clause {
+    fn from(value: VersionReq) -> Self {
+        Clause::VersionReq(value)
+    }
+}
+
+impl From<serde_json::Value> for Clause {
+    fn from(value: serde_json::Value) -> Self {
+        Clause::Raw(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_nested_bool() {
+        let python = MatchProgram {
+            program: "python".to_owned(),
+        };
+        let python3 = MatchProgram {
+            program: "python3".to_owned(),
+        };
+        let haskell = json!({ "prefix": { "package_attr_name": "haskellPackages." } });
+
+        let clause = Clause::must([
+            Clause::should([python.clone(), python3.clone()]),
+            Clause::must_not([haskell.clone()]),
+        ]);
+
+        assert_eq!(
+            clause.to_json(),
+            json!({
+                "bool": {
+                    "must": [
+                        {
+                            "bool": {
+                                "should": [python.to_json(), python3.to_json()],
+                                "minimum_should_match": 1,
+                            }
+                        },
+                        { "bool": { "must_not": [haskell] } },
+                    ]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_leaves_compile_to_their_json() {
+        let name = MatchName {
+            name: "rust".to_owned(),
+        };
+        assert_eq!(Clause::from(name.clone()).to_json(), name.to_json());
+        assert_eq!(
+            Clause::filter([name.clone()]).to_json(),
+            json!({ "bool": { "filter": [name.to_json()] } })
+        );
+    }
+}
@@ -0,0 +1,625 @@
+use std::{
+    fmt,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use base64::prelude::*;
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use crate::{
+    response, Cache, CachingTransport, LenientSearchResults, Method, NixPackage, NixSearchError,
+    Query, SearchPages, SearchResults, SearchWithin, Transport, TransportRequest, UreqTransport,
+};
+
+/// username and password for the elastic search cluster
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for Credentials {
+    /// leaves out the password, so it doesn't end up in logs
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+impl Credentials {
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+
+    /// the value of a basic `Authorization` header
+    fn authorization(&self) -> String {
+        format!(
+            "Basic {}",
+            BASE64_STANDARD.encode(format!("{}:{}", self.username, self.password))
+        )
+    }
+}
+
+/// Where and how to reach the elastic search cluster behind search.nixos.org.
+///
+/// [`Query::send`] goes through [`Client::default`], which points at the
+/// cluster this crate was released against. When that cluster moves, or to
+/// use a mirror, build your own client, in code or from the environment:
+///
+/// ```rust,no_run
+/// use nix_elastic_search::{Client, Query};
+///
+/// let client = Client::builder()
+///     .base_url("https://search.example.org/")
+///     .credentials("user", "hunter2")
+///     .build()
+///     .unwrap();
+///
+/// let query = Query::builder().channel("23.11").name("rust").build().unwrap();
+/// let packages = client.send(&query).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct Client {
+    base_url: Url,
+    index_prefix: String,
+    credentials: Option<Credentials>,
+    timeout: Option<Duration>,
+    user_agent: String,
+    pub(crate) transport: Arc<dyn Transport + Send + Sync>,
+    /// `None` when a custom transport is used, the async methods go through it too
+    #[cfg(feature = "async")]
+    pub(crate) async_client: Option<reqwest::Client>,
+    /// the cache the async methods use, the transport has its own
+    #[cfg(feature = "async")]
+    pub(crate) cache: Option<Cache>,
+}
+
+impl Client {
+    /// the cluster search.nixos.org used when this crate was released
+    pub const DEFAULT_BASE_URL: &'static str =
+        "https://nixos-search-7-1733963800.us-east-1.bonsaisearch.net:443/";
+    /// the index aliases are called `latest-<schema>-nixos-<channel>`
+    pub const DEFAULT_INDEX_PREFIX: &'static str = "latest-*-";
+    pub const DEFAULT_USERNAME: &'static str = "aWVSALXpZv";
+    pub const DEFAULT_PASSWORD: &'static str = "X8gPHnzL52wFEekuxsfQ9cSh";
+    pub const DEFAULT_USER_AGENT: &'static str =
+        concat!("nix-elastic-search/", env!("CARGO_PKG_VERSION"));
+
+    pub const ENV_BASE_URL: &'static str = "NIX_SEARCH_URL";
+    pub const ENV_INDEX_PREFIX: &'static str = "NIX_SEARCH_INDEX_PREFIX";
+    pub const ENV_USERNAME: &'static str = "NIX_SEARCH_USERNAME";
+    pub const ENV_PASSWORD: &'static str = "NIX_SEARCH_PASSWORD";
+    /// in seconds
+    pub const ENV_TIMEOUT: &'static str = "NIX_SEARCH_TIMEOUT";
+    pub const ENV_USER_AGENT: &'static str = "NIX_SEARCH_USER_AGENT";
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    /// The default client, with anything set in the `NIX_SEARCH_*`
+    /// environment variables taking precedence, see [`ClientBuilder::from_env`].
+    pub fn from_env() -> Result<Self, NixSearchError> {
+        ClientBuilder::from_env()?.build()
+    }
+
+    /// the client [`Query::send`] uses
+    pub(crate) fn shared() -> &'static Client {
+        static CLIENT: OnceLock<Client> = OnceLock::new();
+        CLIENT.get_or_init(Client::default)
+    }
+
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    pub fn index_prefix(&self) -> &str {
+        &self.index_prefix
+    }
+
+    pub fn credentials(&self) -> Option<&Credentials> {
+        self.credentials.as_ref()
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    /// Search nix packages for `query`
+    pub fn send(&self, query: &Query) -> Result<Vec<NixPackage>, NixSearchError> {
+        self.send_with(&*self.transport, query)
+    }
+
+    /// Search nix packages for `query`, keeping the total hit count,
+    /// the query time and the score of every hit, see [`Query::send_results`]
+    /// (and what it says about the total with a version requirement).
+    pub fn send_results(&self, query: &Query) -> Result<SearchResults, NixSearchError> {
+        self.send_results_with(&*self.transport, query)
+    }
+
+    /// Search nix packages for `query`, skipping the hits
+    /// that don't deserialize, see [`Query::send_lenient`].
+    pub fn send_lenient(&self, query: &Query) -> Result<LenientSearchResults, NixSearchError> {
+        self.send_lenient_with(&*self.transport, query)
+    }
+
+    /// Lazily walk every package matching `query`, see [`Query::iter`].
+    pub fn iter<'a>(&'a self, query: &'a Query) -> SearchPages<'a> {
+        SearchPages::new(self, &*self.transport, query)
+    }
+
+    pub(crate) fn send_with(
+        &self,
+        transport: &dyn Transport,
+        query: &Query,
+    ) -> Result<Vec<NixPackage>, NixSearchError> {
+        Ok(self.send_results_with(transport, query)?.into_documents())
+    }
+
+    pub(crate) fn send_results_with(
+        &self,
+        transport: &dyn Transport,
+        query: &Query,
+    ) -> Result<SearchResults, NixSearchError> {
+        let mut results = self.search(transport, &query.search_within, &query.payload())?;
+        query.finish_hits(&mut results.hits);
+        Ok(results)
+    }
+
+    pub(crate) fn send_lenient_with(
+        &self,
+        transport: &dyn Transport,
+        query: &Query,
+    ) -> Result<LenientSearchResults, NixSearchError> {
+        let results =
+            self.search::<serde_json::Value>(transport, &query.search_within, &query.payload())?;
+        let mut lenient = results.into_lenient();
+        query.finish_hits(&mut lenient.results.hits);
+        Ok(lenient)
+    }
+
+    pub(crate) fn search<T: DeserializeOwned>(
+        &self,
+        transport: &dyn Transport,
+        search_within: &SearchWithin,
+        payload: &serde_json::Value,
+    ) -> Result<SearchResults<T>, NixSearchError> {
+        let request = self.request(
+            Method::Post,
+            self.search_url(search_within)?,
+            Some(payload.clone()),
+        );
+
+        // elastic search explains what went wrong in the body,
+        // the status only matters when it doesn't.
+        let res = transport.send(&request)?;
+
+        response::parse_search_response(res.status, res.body)
+    }
+
+    /// a request to the cluster with the headers every request needs
+    pub(crate) fn request(
+        &self,
+        method: Method,
+        url: Url,
+        body: Option<serde_json::Value>,
+    ) -> TransportRequest {
+        let mut headers = vec![
+            ("Content-Type".to_owned(), "application/json".to_owned()),
+            ("Accept".to_owned(), "application/json".to_owned()),
+            ("User-Agent".to_owned(), self.user_agent.clone()),
+        ];
+        if let Some(credentials) = &self.credentials {
+            headers.push(("Authorization".to_owned(), credentials.authorization()));
+        }
+
+        TransportRequest {
+            method,
+            url,
+            headers,
+            body,
+        }
+    }
+
+    /// the `_search` endpoint of the indices `search_within` covers
+    pub(crate) fn search_url(&self, search_within: &SearchWithin) -> Result<Url, NixSearchError> {
+        let index = match search_within {
+            SearchWithin::Channel(channel) => format!("{}nixos-{channel}/", self.index_prefix),
+            SearchWithin::Flakes => format!("{}group-manual/", self.index_prefix),
+        };
+
+        self.base_url
+            .join(&index)
+            .and_then(|url| url.join("_search"))
+            .map_err(|err| NixSearchError::InvalidConfig {
+                reason: format!("can't build a search url from `{index}`: {err}"),
+            })
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        ClientBuilder::new()
+            .build()
+            .expect("the default client configuration is valid")
+    }
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("base_url", &self.base_url.as_str())
+            .field("index_prefix", &self.index_prefix)
+            .field(
+                "credentials",
+                &self.credentials.as_ref().map(|c| &c.username),
+            )
+            .field("timeout", &self.timeout)
+            .field("user_agent", &self.user_agent)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builds a [`Client`], anything not set keeps the value of [`Client::default`].
+#[derive(Clone)]
+pub struct ClientBuilder {
+    base_url: String,
+    index_prefix: String,
+    credentials: Option<Credentials>,
+    timeout: Option<Duration>,
+    user_agent: String,
+    transport: Option<Arc<dyn Transport + Send + Sync>>,
+    cache: Option<Cache>,
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        Self {
+            base_url: Client::DEFAULT_BASE_URL.to_owned(),
+            index_prefix: Client::DEFAULT_INDEX_PREFIX.to_owned(),
+            credentials: Some(Credentials::new(
+                Client::DEFAULT_USERNAME,
+                Client::DEFAULT_PASSWORD,
+            )),
+            timeout: None,
+            user_agent: Client::DEFAULT_USER_AGENT.to_owned(),
+            transport: None,
+            cache: None,
+        }
+    }
+
+    /// Start from the defaults, overridden by whichever of
+    /// `NIX_SEARCH_URL`, `NIX_SEARCH_INDEX_PREFIX`, `NIX_SEARCH_USERNAME`,
+    /// `NIX_SEARCH_PASSWORD`, `NIX_SEARCH_TIMEOUT` (seconds) and
+    /// `NIX_SEARCH_USER_AGENT` are set.
+    pub fn from_env() -> Result<Self, NixSearchError> {
+        Self::new().env(|key| std::env::var(key).ok())
+    }
+
+    /// [`ClientBuilder::from_env`] with a custom lookup, so it can be tested
+    /// without touching the process environment.
+    fn env(mut self, var: impl Fn(&str) -> Option<String>) -> Result<Self, NixSearchError> {
+        if let Some(base_url) = var(Client::ENV_BASE_URL) {
+            self.base_url = base_url;
+        }
+        if let Some(index_prefix) = var(Client::ENV_INDEX_PREFIX) {
+            self.index_prefix = index_prefix;
+        }
+        match (var(Client::ENV_USERNAME), var(Client::ENV_PASSWORD)) {
+            (Some(username), Some(password)) => {
+                self.credentials = Some(Credentials::new(username, password))
+            }
+            (None, None) => {}
+            _ => {
+                return Err(NixSearchError::InvalidConfig {
+                    reason: format!(
+                        "`{}` and `{}` must be set together",
+                        Client::ENV_USERNAME,
+                        Client::ENV_PASSWORD
+                    ),
+                })
+            }
+        }
+        if let Some(timeout) = var(Client::ENV_TIMEOUT) {
+            let seconds = timeout
+                .parse::<f64>()
+                .ok()
+                .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+                .ok_or_else(|| NixSearchError::InvalidConfig {
+                    reason: format!(
+                        "`{}` must be a positive number of seconds, got `{timeout}`",
+                        Client::ENV_TIMEOUT
+                    ),
+                })?;
+            self.timeout = Some(Duration::from_secs_f64(seconds));
+        }
+        if let Some(user_agent) = var(Client::ENV_USER_AGENT) {
+            self.user_agent = user_agent;
+        }
+        Ok(self)
+    }
+
+    /// the root of the elastic search cluster
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// what comes before `nixos-<channel>` or `group-manual` in the index name
+    pub fn index_prefix(mut self, index_prefix: impl Into<String>) -> Self {
+        self.index_prefix = index_prefix.into();
+        self
+    }
+
+    pub fn credentials(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.credentials = Some(Credentials::new(username, password));
+        self
+    }
+
+    /// for a cluster that doesn't need a login
+    pub fn no_credentials(mut self) -> Self {
+        self.credentials = None;
+        self
+    }
+
+    /// how long a whole request may take, ignored when a custom
+    /// [`ClientBuilder::transport`] is used.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Send requests through `transport` instead of ureq.
+    ///
+    /// The async methods use it too, instead of reqwest, and block while it sends.
+    /// That's what lets an in-memory transport answer them in tests.
+    pub fn transport(mut self, transport: impl Transport + Send + Sync + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// keep responses on disk, see [`Cache`].
+    ///
+    /// This wraps whichever transport is used, and the async methods use it too.
+    pub fn cache(mut self, cache: Cache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn build(self) -> Result<Client, NixSearchError> {
+        let mut base_url =
+            Url::parse(&self.base_url).map_err(|err| NixSearchError::InvalidConfig {
+                reason: format!("invalid base url `{}`: {err}", self.base_url),
+            })?;
+        if base_url.cannot_be_a_base() {
+            return Err(NixSearchError::InvalidConfig {
+                reason: format!("`{}` can't be used as a base url", self.base_url),
+            });
+        }
+        // without the trailing slash, joining the index would replace the last segment.
+        if !base_url.path().ends_with('/') {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+
+        if self.index_prefix.contains('/') {
+            return Err(NixSearchError::InvalidConfig {
+                reason: format!(
+                    "the index prefix must not contain `/`, got `{}`",
+                    self.index_prefix
+                ),
+            });
+        }
+
+        #[cfg(feature = "async")]
+        let async_client = match self.transport {
+            Some(_) => None,
+            None => {
+                let mut client = reqwest::Client::builder().user_agent(&self.user_agent);
+                if let Some(timeout) = self.timeout {
+                    client = client.timeout(timeout);
+                }
+                Some(client.build()?)
+            }
+        };
+        #[cfg(feature = "async")]
+        let cache = self.cache.clone();
+
+        let transport = match self.transport {
+            Some(transport) => transport,
+            None => {
+                let mut agent = ureq::AgentBuilder::new().user_agent(&self.user_agent);
+                if let Some(timeout) = self.timeout {
+                    agent = agent.timeout(timeout);
+                }
+                Arc::new(UreqTransport::new(agent.build()))
+            }
+        };
+        let transport: Arc<dyn Transport + Send + Sync> = match self.cache {
+            Some(cache) => Arc::new(CachingTransport::new(cache, transport)),
+            None => transport,
+        };
+
+        Ok(Client {
+            base_url,
+            index_prefix: self.index_prefix,
+            credentials: self.credentials,
+            timeout: self.timeout,
+            user_agent: self.user_agent,
+            transport,
+            #[cfg(feature = "async")]
+            async_client,
+            #[cfg(feature = "async")]
+            cache,
+        })
+    }
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ClientBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientBuilder")
+            .field("base_url", &self.base_url)
+            .field("index_prefix", &self.index_prefix)
+            .field(
+                "credentials",
+                &self.credentials.as_ref().map(|c| &c.username),
+            )
+            .field("timeout", &self.timeout)
+            .field("user_agent", &self.user_agent)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::MemoryTransport;
+
+    #[test]
+    fn test_default_urls() {
+        let client = Client::default();
+        assert_eq!(
+            client
+                .search_url(&SearchWithin::Channel("23.11".to_owned()))
+                .unwrap()
+                .as_str(),
+            "https://nixos-search-7-1733963800.us-east-1.bonsaisearch.net/latest-*-nixos-23.11/_search"
+        );
+        assert_eq!(
+            client.search_url(&SearchWithin::Flakes).unwrap().as_str(),
+            "https://nixos-search-7-1733963800.us-east-1.bonsaisearch.net/latest-*-group-manual/_search"
+        );
+    }
+
+    #[test]
+    fn test_mirror_with_path() {
+        let client = Client::builder()
+            .base_url("http://localhost:9200/mirror")
+            .index_prefix("nixos-")
+            .no_credentials()
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            client
+                .search_url(&SearchWithin::Channel("unstable".to_owned()))
+                .unwrap()
+                .as_str(),
+            "http://localhost:9200/mirror/nixos-nixos-unstable/_search"
+        );
+
+        let request = client.request(Method::Get, client.base_url().clone(), None);
+        assert!(request.header("Authorization").is_none());
+    }
+
+    #[test]
+    fn test_debug_hides_password() {
+        let credentials = Credentials::new("user", "hunter2");
+        let client = Client::builder()
+            .credentials("user", "hunter2")
+            .build()
+            .unwrap();
+
+        for debug in [
+            format!("{credentials:?}"),
+            format!("{:?}", Some(&credentials)),
+            format!("{client:?}"),
+            format!("{:?}", Client::builder().credentials("user", "hunter2")),
+        ] {
+            assert!(debug.contains("user"), "{debug}");
+            assert!(!debug.contains("hunter2"), "{debug}");
+        }
+    }
+
+    #[test]
+    fn test_from_env() {
+        let env = HashMap::from([
+            (Client::ENV_BASE_URL, "https://search.example.org"),
+            (Client::ENV_USERNAME, "user"),
+            (Client::ENV_PASSWORD, "hunter2"),
+            (Client::ENV_TIMEOUT, "2.5"),
+            (Client::ENV_USER_AGENT, "our-tooling/1.0"),
+        ]);
+        let client = ClientBuilder::new()
+            .env(|key| env.get(key).map(|v| v.to_string()))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(client.base_url().as_str(), "https://search.example.org/");
+        assert_eq!(client.index_prefix(), Client::DEFAULT_INDEX_PREFIX);
+        assert_eq!(
+            client.credentials(),
+            Some(&Credentials::new("user", "hunter2"))
+        );
+        assert_eq!(client.timeout(), Some(Duration::from_millis(2500)));
+        assert_eq!(client.user_agent(), "our-tooling/1.0");
+    }
+
+    #[test]
+    fn test_from_env_rejects_bad_values() {
+        let half_credentials = HashMap::from([(Client::ENV_USERNAME, "user")]);
+        let bad_timeout = HashMap::from([(Client::ENV_TIMEOUT, "soon")]);
+
+        for env in [half_credentials, bad_timeout] {
+            assert!(matches!(
+                ClientBuilder::new().env(|key| env.get(key).map(|v| v.to_string())),
+                Err(NixSearchError::InvalidConfig { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_client_sends_through_its_transport() {
+        let transport = Arc::new(MemoryTransport::new());
+        transport.push_response(200, r#"{ "hits": { "hits": [] } }"#);
+
+        let client = Client::builder()
+            .base_url("http://localhost:9200")
+            .credentials("user", "hunter2")
+            .user_agent("our-tooling/1.0")
+            .transport(transport.clone())
+            .build()
+            .unwrap();
+        let query = Query::builder()
+            .channel("23.11")
+            .name("rust")
+            .build()
+            .unwrap();
+        assert!(client.send(&query).unwrap().is_empty());
+
+        let request = &transport.requests()[0];
+        assert_eq!(
+            request.url.as_str(),
+            "http://localhost:9200/latest-*-nixos-23.11/_search"
+        );
+        assert_eq!(request.header("User-Agent"), Some("our-tooling/1.0"));
+        assert_eq!(
+            request.header("Authorization"),
+            Some("Basic dXNlcjpodW50ZXIy")
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use serde_json::json;
+
+/// only packages that build on this platform, like `aarch64-darwin`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterPlatform(pub String);
+
+impl FilterPlatform {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({ "term": { "package_platforms": self.0 } })
+    }
+}
+
+/// only packages with this license, by full name, like `MIT License`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterLicense(pub String);
+
+impl FilterLicense {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({ "term": { "package_license_set": self.0 } })
+    }
+}
+
+/// only packages from this attribute set, like `python311Packages`.
+///
+/// Top level packages are in the `No package set` set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterAttrSet(pub String);
+
+impl FilterAttrSet {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({ "term": { "package_attr_set": self.0 } })
+    }
+}
+
+/// only packages maintained by this maintainer, by name as
+/// it appears in `package_maintainers_set`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterMaintainer(pub String);
+
+impl FilterMaintainer {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({ "term": { "package_maintainers_set": self.0 } })
+    }
+}
+
+/// only packages that install a program with exactly this name, like `rg`.
+///
+/// Unlike [`MatchProgram`](crate::MatchProgram) nothing is matched on a prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterProgram(pub String);
+
+impl FilterProgram {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({ "term": { "package_programs": self.0 } })
+    }
+}
+
+/// only the package with exactly this attribute name, like `python311Packages.requests`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterAttrName(pub String);
+
+impl FilterAttrName {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({ "term": { "package_attr_name": self.0 } })
+    }
+}
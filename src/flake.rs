@@ -0,0 +1,158 @@
+use std::fmt;
+
+use serde_json::json;
+
+use crate::{FlakeResolved, NixPackage};
+
+/// Where a flake comes from, parsed from [`NixPackage::flake_resolved`].
+///
+/// Displays as a flake reference:
+///
+/// ```rust
+/// use nix_elastic_search::FlakeOrigin;
+///
+/// let origin = FlakeOrigin::GitHub {
+///     owner: "nix-community".to_owned(),
+///     repo: "home-manager".to_owned(),
+/// };
+/// assert_eq!(origin.to_string(), "github:nix-community/home-manager");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FlakeOrigin {
+    GitHub { owner: String, repo: String },
+    GitLab { owner: String, repo: String },
+    Git { url: String },
+    Path { path: String },
+}
+
+impl FlakeOrigin {
+    /// the flake reference for an attribute of this
+    /// flake, like `github:owner/repo#attr`
+    pub fn flake_ref(&self, attr: &str) -> String {
+        format!("{self}#{attr}")
+    }
+}
+
+impl fmt::Display for FlakeOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlakeOrigin::GitHub { owner, repo } => write!(f, "github:{owner}/{repo}"),
+            FlakeOrigin::GitLab { owner, repo } => write!(f, "gitlab:{owner}/{repo}"),
+            FlakeOrigin::Git { url } if url.starts_with("git+") => write!(f, "{url}"),
+            FlakeOrigin::Git { url } => write!(f, "git+{url}"),
+            FlakeOrigin::Path { path } => write!(f, "path:{path}"),
+        }
+    }
+}
+
+impl FlakeResolved {
+    /// The typed origin, `None` for a kind of
+    /// flake this crate doesn't know, or one missing its fields.
+    pub fn origin(&self) -> Option<FlakeOrigin> {
+        let owner_repo = || Some((self.owner.clone()?, self.repo.clone()?));
+
+        match self.type_field.as_str() {
+            "github" => owner_repo().map(|(owner, repo)| FlakeOrigin::GitHub { owner, repo }),
+            "gitlab" => owner_repo().map(|(owner, repo)| FlakeOrigin::GitLab { owner, repo }),
+            "git" => self.url.clone().map(|url| FlakeOrigin::Git { url }),
+            "path" => self
+                .extra
+                .get("path")
+                .and_then(serde_json::Value::as_str)
+                .map(|path| FlakeOrigin::Path {
+                    path: path.to_owned(),
+                }),
+            _ => None,
+        }
+    }
+}
+
+impl NixPackage {
+    /// where the flake this package comes from was fetched
+    /// from, only set when searching flakes
+    pub fn flake_origin(&self) -> Option<FlakeOrigin> {
+        self.flake_resolved.as_ref()?.origin()
+    }
+
+    /// A reference to this package that `nix run` and
+    /// `nix shell` take, like `github:owner/repo#attr`.
+    ///
+    /// Only set when searching flakes.
+    pub fn flake_ref(&self) -> Option<String> {
+        Some(self.flake_origin()?.flake_ref(&self.package_attr_name))
+    }
+}
+
+/// only packages from the flake with this name, for [`SearchWithin::Flakes`](crate::SearchWithin::Flakes)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchFlake {
+    pub name: String,
+}
+
+impl MatchFlake {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({ "match_phrase": { "flake_name": self.name } })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_flake_origin() {
+        let resolved =
+            |value: serde_json::Value| -> FlakeResolved { serde_json::from_value(value).unwrap() };
+
+        assert_eq!(
+            resolved(json!({ "type": "github", "owner": "numtide", "repo": "treefmt" })).origin(),
+            Some(FlakeOrigin::GitHub {
+                owner: "numtide".to_owned(),
+                repo: "treefmt".to_owned()
+            })
+        );
+        assert_eq!(
+            resolved(json!({ "type": "gitlab", "owner": "pi", "repo": "tool" }))
+                .origin()
+                .unwrap()
+                .to_string(),
+            "gitlab:pi/tool"
+        );
+        assert_eq!(
+            resolved(json!({ "type": "git", "url": "https://git.sr.ht/~x/y" }))
+                .origin()
+                .unwrap()
+                .to_string(),
+            "git+https://git.sr.ht/~x/y"
+        );
+        assert_eq!(
+            resolved(json!({ "type": "path", "path": "/nix/store/abc-source" }))
+                .origin()
+                .unwrap()
+                .to_string(),
+            "path:/nix/store/abc-source"
+        );
+        assert_eq!(resolved(json!({ "type": "github" })).origin(), None);
+        assert_eq!(resolved(json!({ "type": "tarball" })).origin(), None);
+    }
+
+    #[test]
+    fn test_package_flake_ref() {
+        let mut package = NixPackage {
+            package_attr_name: "treefmt".to_owned(),
+            ..Default::default()
+        };
+        assert_eq!(package.flake_ref(), None);
+
+        package.flake_resolved = Some(FlakeResolved {
+            type_field: "github".to_owned(),
+            owner: Some("numtide".to_owned()),
+            repo: Some("treefmt".to_owned()),
+            ..Default::default()
+        });
+        assert_eq!(
+            package.flake_ref().as_deref(),
+            Some("github:numtide/treefmt#treefmt")
+        );
+    }
+}
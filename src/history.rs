@@ -0,0 +1,320 @@
+use std::{cmp::Reverse, fmt, str::FromStr};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{NixSearchError, NixVersion, PackageVersion};
+
+/// A calendar day, like [`PackageVersion::date`].
+///
+/// Parses and prints as `YYYY-MM-DD`, in json too, and orders by time:
+///
+/// ```rust
+/// use nix_elastic_search::Date;
+///
+/// let date: Date = "2023-06-01".parse().unwrap();
+/// assert!(date < "2023-12-24".parse().unwrap());
+/// assert_eq!(date.to_string(), "2023-06-01");
+/// assert!("2023-02-29".parse::<Date>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    year: u16,
+    month: u8,
+    day: u8,
+}
+
+impl Date {
+    /// `None` when the day doesn't exist, like the 31st of April
+    pub fn new(year: u16, month: u8, day: u8) -> Option<Self> {
+        let leap =
+            year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+        let days = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap => 29,
+            2 => 28,
+            _ => return None,
+        };
+        (1..=days)
+            .contains(&day)
+            .then_some(Self { year, month, day })
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// days since 1970-01-01, `days_from_civil` from
+    /// <https://howardhinnant.github.io/date_algorithms.html>
+    fn days_since_epoch(&self) -> i64 {
+        let month = i64::from(self.month);
+        let year = i64::from(self.year) - i64::from(month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// how many days apart the two dates are
+    pub fn days_between(&self, other: &Date) -> u64 {
+        self.days_since_epoch().abs_diff(other.days_since_epoch())
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = NixSearchError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || NixSearchError::InvalidDate { date: s.to_owned() };
+
+        let parts = s.split('-').collect::<Vec<_>>();
+        let [year, month, day] = parts.as_slice() else {
+            return Err(invalid());
+        };
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return Err(invalid());
+        }
+        if !parts
+            .iter()
+            .all(|part| part.bytes().all(|b| b.is_ascii_digit()))
+        {
+            return Err(invalid());
+        }
+
+        let number = |part: &str| part.parse().map_err(|_| invalid());
+        Date::new(number(year)?, number(month)? as u8, number(day)? as u8).ok_or_else(invalid)
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let date = String::deserialize(deserializer)?;
+        date.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Questions about a version history, like the one
+/// [`lookup_package_versions`](crate::lookup_package_versions) returns.
+///
+/// ```rust
+/// use nix_elastic_search::{PackageVersion, VersionHistory};
+///
+/// let version = |version: &str, revision: &str, date: &str| PackageVersion {
+///     version: version.to_owned(),
+///     revision: revision.repeat(40),
+///     date: date.parse().unwrap(),
+///     date_raw: date.to_owned(),
+/// };
+/// let history = vec![
+///     version("14.1.0", "a", "2024-01-09"),
+///     version("14.0.3", "b", "2023-12-02"),
+///     version("13.0.0", "c", "2023-05-20"),
+///     version("13.0.0", "d", "2021-06-24"),
+/// ];
+///
+/// // the newest revision before the first of june 2023
+/// let as_of = history.as_of("2023-06-01".parse().unwrap()).unwrap();
+/// assert_eq!(as_of.revision, "c".repeat(40));
+/// ```
+pub trait VersionHistory {
+    /// sort newest first, by date and then by version
+    fn sort_by_date(&mut self);
+
+    /// the newest revision of every version, newest version first
+    fn latest_per_version(&self) -> Vec<&PackageVersion>;
+
+    /// the oldest revision of every version, when it first
+    /// showed up, newest version first
+    fn first_appearances(&self) -> Vec<&PackageVersion>;
+
+    /// the newest revision from `date` or before it, what the channel had that day
+    fn as_of(&self, date: Date) -> Option<&PackageVersion>;
+
+    /// the revision with the date nearest to `date`, before or after, the older one on a tie
+    fn closest_to(&self, date: Date) -> Option<&PackageVersion>;
+}
+
+impl VersionHistory for [PackageVersion] {
+    fn sort_by_date(&mut self) {
+        self.sort_by_cached_key(|v| Reverse((v.date, v.nix_version())));
+    }
+
+    fn latest_per_version(&self) -> Vec<&PackageVersion> {
+        per_version(self, |candidate, kept| candidate > kept)
+    }
+
+    fn first_appearances(&self) -> Vec<&PackageVersion> {
+        per_version(self, |candidate, kept| candidate < kept)
+    }
+
+    fn as_of(&self, date: Date) -> Option<&PackageVersion> {
+        self.iter()
+            .filter(|version| version.date <= date)
+            .max_by_key(|version| version.date)
+    }
+
+    fn closest_to(&self, date: Date) -> Option<&PackageVersion> {
+        self.iter()
+            .min_by_key(|version| (version.date.days_between(&date), version.date))
+    }
+}
+
+/// one revision per version, the one `replaces` prefers, newest version first
+fn per_version(
+    versions: &[PackageVersion],
+    replaces: impl Fn(Date, Date) -> bool,
+) -> Vec<&PackageVersion> {
+    let mut kept = Vec::<(NixVersion, &PackageVersion)>::new();
+    for version in versions {
+        // the same version as `NixVersion` sees it, so `1.02` is `1.2`
+        let nix_version = version.nix_version();
+        match kept.iter_mut().find(|(k, _)| *k == nix_version) {
+            Some(entry) if replaces(version.date, entry.1.date) => entry.1 = version,
+            Some(_) => {}
+            None => kept.push((nix_version, version)),
+        }
+    }
+
+    kept.sort_by(|(a, _), (b, _)| b.cmp(a));
+    kept.into_iter().map(|(_, version)| version).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn date(date: &str) -> Date {
+        date.parse().unwrap()
+    }
+
+    fn version(version: &str, revision: &str, date: &str) -> PackageVersion {
+        PackageVersion {
+            version: version.to_owned(),
+            revision: revision.repeat(40),
+            date: date.parse().unwrap(),
+            date_raw: date.to_owned(),
+        }
+    }
+
+    fn history() -> Vec<PackageVersion> {
+        vec![
+            version("13.0.0", "d", "2021-06-24"),
+            version("14.1.0", "a", "2024-01-09"),
+            version("13.0.0", "c", "2023-05-20"),
+            version("14.0.3", "b", "2023-12-02"),
+        ]
+    }
+
+    fn revisions(versions: &[&PackageVersion]) -> Vec<char> {
+        versions
+            .iter()
+            .map(|v| v.revision.chars().next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_date() {
+        assert_eq!(Date::new(2024, 2, 29), Some(date("2024-02-29")));
+        assert_eq!(Date::new(2023, 2, 29), None);
+        assert_eq!(date("1970-01-01").days_since_epoch(), 0);
+        assert_eq!(date("2000-03-01").days_since_epoch(), 11_017);
+        assert_eq!(date("2023-12-31").days_between(&date("2024-03-01")), 61);
+        assert!(date("2023-12-31") < date("2024-01-01"));
+        for good in ["2024-01-09", "2024-02-29", "2000-02-29", "1999-12-31"] {
+            assert_eq!(date(good).to_string(), good);
+        }
+        for bad in [
+            "2023-02-29",
+            "1900-02-29",
+            "2024-13-01",
+            "2024-04-31",
+            "2024-1-09",
+            "2024-+1-09",
+            "09-01-2024",
+            "yesterday",
+        ] {
+            assert!(
+                matches!(bad.parse::<Date>(), Err(NixSearchError::InvalidDate { .. })),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_date_json() {
+        let json = serde_json::to_string(&date("2024-02-29")).unwrap();
+        assert_eq!(json, r#""2024-02-29""#);
+        assert_eq!(
+            serde_json::from_str::<Date>(&json).unwrap(),
+            date("2024-02-29")
+        );
+        assert!(serde_json::from_str::<Date>(r#""2023-02-29""#).is_err());
+    }
+
+    #[test]
+    fn test_sort_by_date() {
+        let mut history = history();
+        history.sort_by_date();
+        assert_eq!(
+            history
+                .iter()
+                .map(|v| v.revision.chars().next().unwrap())
+                .collect::<Vec<_>>(),
+            ['a', 'b', 'c', 'd']
+        );
+    }
+
+    #[test]
+    fn test_per_version() {
+        let history = history();
+        assert_eq!(revisions(&history.latest_per_version()), ['a', 'b', 'c']);
+        assert_eq!(revisions(&history.first_appearances()), ['a', 'b', 'd']);
+    }
+
+    #[test]
+    fn test_as_of_and_closest() {
+        let history = history();
+        assert_eq!(
+            history.as_of(date("2023-06-01")).unwrap().revision,
+            "c".repeat(40)
+        );
+        assert_eq!(
+            history.as_of(date("2024-01-09")).unwrap().revision,
+            "a".repeat(40)
+        );
+        assert!(history.as_of(date("2020-01-01")).is_none());
+
+        assert_eq!(
+            history.closest_to(date("2023-11-30")).unwrap().revision,
+            "b".repeat(40)
+        );
+        assert_eq!(
+            history.closest_to(date("2020-01-01")).unwrap().revision,
+            "d".repeat(40)
+        );
+        assert!(Vec::<PackageVersion>::new()
+            .closest_to(date("2020-01-01"))
+            .is_none());
+    }
+}
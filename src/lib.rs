@@ -1,4 +1,5 @@
 #![allow(dead_code)]
+#![allow(clippy::result_large_err)]
 
 //! # Nix Search for Rust
 //!
@@ -17,81 +18,143 @@
 //!
 //! Note you can turn off the web scraper if you don't need version search by setting default
 //! features to [], and then including the other features you still want.
+//!
+//! The `async` feature adds [`Query::send_async`] (and `lookup_package_versions_async`),
+//! which run on [reqwest](https://docs.rs/reqwest) instead of blocking on ureq, so they
+//! can be awaited from tokio without `spawn_blocking`.
 //! ## Usage
 //!
 //! ```rust
-//! use nix_elastic_search::{Query, SearchWithin, MatchSearch};
-//!
-//! let query = Query {
-//!     max_results: 10,
-//!     search_within: SearchWithin::Channel("23.11".to_owned()),
+//! use nix_elastic_search::Query;
 //!
-//!     search: Some(MatchSearch {
-//!         search: "gleam".to_owned(),
-//!     }),
-//!     program: None,
-//!     name: None,
-//!     version: None,
-//!     query_string: None,
-//! };
+//! let query = Query::builder()
+//!     .channel("23.11")
+//!     .search("gleam")
+//!     .limit(10)
+//!     .build()
+//!     .unwrap();
 //!
 //! assert!(query.send().is_ok());
 //! ```
 //!
 //! ```rust
-//! use nix_elastic_search::{Query, SearchWithin, MatchName};
+//! use nix_elastic_search::Query;
 //!
-//! let query = Query {
-//!     max_results: 10,
-//!     search_within: SearchWithin::Channel("23.11".to_owned()),
-//!     search: None,
-//!     program: None,
-//!     name: Some(MatchName { name: "rust".to_owned() }),
-//!     version: None,
-//!     query_string: None,
-//! };
+//! let query = Query::builder()
+//!     .channel("23.11")
+//!     .name("rust")
+//!     .limit(10)
+//!     .build()
+//!     .unwrap();
 //!
 //! query.send().unwrap();
 //! ```
+//!
+//! Filters that aren't set are left out, and the builder checks the input,
+//! like a channel or a limit past what elastic search allows.
 
 #[derive(Debug)]
 pub struct SerdeNixPackagePath {
     text: String,
+    error_path: fn(&str) -> String,
 }
 
 impl SerdeNixPackagePath {
     pub fn new(text: String) -> Self {
-        Self { text }
+        Self::for_document::<NixPackage>(text)
     }
 
-    pub fn get_error_path(&self) -> String {
-        let jd = &mut serde_json::Deserializer::from_str(&self.text);
-        let result: Result<response::SearchResponse, _> = serde_path_to_error::deserialize(jd);
+    /// for a search response holding `T`s instead of [`NixPackage`]s
+    pub(crate) fn for_document<T: serde::de::DeserializeOwned>(text: String) -> Self {
+        Self::for_response::<response::SearchResponse<T>>(text)
+    }
 
-        match result {
-            Ok(_) => "<no path found>".to_owned(),
-            Err(err) => err.path().to_string(),
+    /// for a response that isn't a search response at all
+    pub(crate) fn for_response<R: serde::de::DeserializeOwned>(text: String) -> Self {
+        Self {
+            text,
+            error_path: error_path::<R>,
         }
     }
+
+    pub fn get_error_path(&self) -> String {
+        (self.error_path)(&self.text)
+    }
 }
+
+fn error_path<R: serde::de::DeserializeOwned>(text: &str) -> String {
+    let jd = &mut serde_json::Deserializer::from_str(text);
+    let result: Result<R, _> = serde_path_to_error::deserialize(jd);
+
+    match result {
+        Ok(_) => "<no path found>".to_owned(),
+        Err(err) => err.path().to_string(),
+    }
+}
+mod builder;
+mod cache;
+mod channel;
+mod clause;
+mod client;
+mod filter;
+mod flake;
+mod nix_version;
+#[cfg(feature = "async")]
+mod nonblocking;
+mod options;
+mod pages;
+mod providers;
 mod response;
+mod transport;
+mod version_req;
+
+pub use builder::{OptionQueryBuilder, QueryBuilder};
+pub use cache::{Cache, CachingTransport};
+pub use channel::{list_channels, AvailableIndex, Channel, IndexGroup};
+pub use clause::Clause;
+pub use client::{Client, ClientBuilder, Credentials};
+pub use filter::{
+    FilterAttrName, FilterAttrSet, FilterLicense, FilterMaintainer, FilterPlatform, FilterProgram,
+};
+pub use flake::{FlakeOrigin, MatchFlake};
+pub use nix_version::{Components, NixVersion};
+pub use options::{MatchOptionName, MatchOptionSearch, OptionQuery};
+pub use pages::SearchPages;
+pub use providers::find_providers;
+pub use transport::{
+    MemoryTransport, Method, Transport, TransportRequest, TransportResponse, UreqTransport,
+};
+pub use version_req::{Comparator, Op, VersionReq};
 
 pub use response::{
-    ElasticSearchResponseError, ElasticSearchResponseErrorResource, ErrorResource, NixPackage,
-    PackageLicense, PackageMaintainer,
+    ElasticSearchResponseError, ElasticSearchResponseErrorResource, ErrorResource, FlakeResolved,
+    LenientSearchResults, NixOption, NixPackage, PackageLicense, PackageMaintainer, SearchHit,
+    SearchResults, ShardFailure, Shards, SkippedHit, TotalHits, TotalHitsRelation,
 };
 
+#[cfg(feature = "version-search")]
+mod history;
+#[cfg(feature = "version-search")]
+mod pin;
 #[cfg(feature = "version-search")]
 pub(crate) mod version;
 #[cfg(feature = "version-search")]
-pub use version::{lookup_package_versions, PackageVersion};
+mod version_source;
+#[cfg(feature = "version-search")]
+pub use history::{Date, VersionHistory};
+#[cfg(all(feature = "version-search", feature = "async"))]
+pub use version::lookup_package_versions_async;
+#[cfg(feature = "version-search")]
+pub use version::{lookup_package_versions, PackageVersion, VersionChannel};
+#[cfg(feature = "version-search")]
+pub use version_source::{Lazamar, Nixhub, NixpkgsGit, VersionSource};
 
-use base64::prelude::*;
 use serde_json::json;
 use thiserror::Error;
 use url::Url;
 
 /// chose whether to search in flakes or by channel
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SearchWithin {
     /// should be something like 23.11 (not nixos-23.11)
     Channel(String),
@@ -106,16 +169,79 @@ pub enum NixSearchError {
         #[from]
         source: ureq::Error,
     },
+    #[cfg(feature = "async")]
+    #[error("reqwest (the async http library) encountered an error: {source}")]
+    ReqwestError {
+        #[from]
+        source: reqwest::Error,
+    },
+    #[error("the http transport failed: {source}")]
+    TransportError {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("Error reading returned response body for search to string")]
+    ErrorReadingSearchBody {
+        #[source]
+        source: std::io::Error,
+    },
     #[error("serde_json (used to parse json) encounted an unexpected error: {source}, at path: {}", path.get_error_path())]
     DeserializationError {
         path: SerdeNixPackagePath,
         source: serde_json::Error,
     },
-    #[error("the elastic search endpoint had a server error")]
+    #[error("the elastic search endpoint had a server error: {error}")]
     ElasticSearchError {
         error: ElasticSearchResponseError,
         status: i64,
     },
+    #[error("the index doesn't exist, is the channel right? {error}")]
+    IndexNotFound { error: ElasticSearchResponseError },
+    #[error("elastic search refused the credentials (status {status})")]
+    AuthenticationFailed {
+        status: i64,
+        error: Option<ElasticSearchResponseError>,
+    },
+    #[error("elastic search is rate limiting requests, try again later")]
+    RateLimited {
+        error: Option<ElasticSearchResponseError>,
+    },
+    #[error("the search timed out")]
+    Timeout {
+        error: Option<ElasticSearchResponseError>,
+    },
+    #[error("elastic search couldn't parse the query: {error}")]
+    MalformedQuery { error: ElasticSearchResponseError },
+    #[error("the search failed on one or more shards: {error}")]
+    ShardFailure {
+        error: ElasticSearchResponseError,
+        status: i64,
+    },
+
+    #[error("the elastic search endpoint had a server error (status {status})")]
+    ServerError { status: i64 },
+    #[error("the elastic search endpoint answered with status {status}, without saying why")]
+    UnexpectedStatus { status: i64 },
+
+    #[error("no package has the attribute `{attr}`")]
+    PackageNotFound { attr: String },
+
+    #[error("invalid query: {reason}")]
+    InvalidQuery { reason: String },
+
+    #[error("`{channel}` is not a channel, expected something like `23.11` or `unstable`")]
+    InvalidChannel { channel: String },
+
+    #[error("`{req}` is not a version requirement: {reason}")]
+    InvalidVersionReq { req: String, reason: String },
+
+    #[error("`{attr}` is not an attribute path: {reason}")]
+    InvalidAttrPath { attr: String, reason: String },
+
+    #[error("`{revision}` is not a nixpkgs revision, expected a commit hash")]
+    InvalidRevision { revision: String },
+
+    #[error("invalid client configuration: {reason}")]
+    InvalidConfig { reason: String },
 
     #[error("invalid package name error. failed to create url for: {package_name}")]
     InvalidPackageNameError {
@@ -124,8 +250,26 @@ pub enum NixSearchError {
         source: url::ParseError,
     },
 
-    #[error("The older versions are scraped from a website, but for some unknown reason the table was not found. That's all we know.")]
-    MissingTableForVersions,
+    #[error(
+        "no versions of `{package_name}` were found, the version search takes the `package_pname`"
+    )]
+    PackageVersionsNotFound { package_name: String },
+
+    /// The older versions are scraped from a website, and it no longer looks like this crate expects.
+    #[error("the version search page changed its layout: {reason}")]
+    VersionsPageChanged { reason: String },
+
+    #[error("`{date}` is not a date like 2024-01-31")]
+    InvalidDate { date: String },
+
+    #[error("{version_source} has no revisions from `{channel}`, only from unstable")]
+    UnsupportedChannel {
+        version_source: String,
+        channel: String,
+    },
+
+    #[error("couldn't read the version history from git: {reason}")]
+    GitHistoryError { reason: String },
 
     #[error("Error reading returned response body for veresion search to string")]
     ErrorReadingVersionBody {
@@ -135,11 +279,66 @@ pub enum NixSearchError {
     },
 }
 
+impl NixSearchError {
+    /// what elastic search said went wrong, if it said anything
+    pub fn elastic_search_error(&self) -> Option<&ElasticSearchResponseError> {
+        match self {
+            NixSearchError::ElasticSearchError { error, .. }
+            | NixSearchError::IndexNotFound { error }
+            | NixSearchError::MalformedQuery { error }
+            | NixSearchError::ShardFailure { error, .. } => Some(error),
+            NixSearchError::AuthenticationFailed { error, .. }
+            | NixSearchError::RateLimited { error }
+            | NixSearchError::Timeout { error } => error.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// the elastic search error type, like `index_not_found_exception`
+    pub fn error_type(&self) -> Option<&str> {
+        self.elastic_search_error()
+            .map(ElasticSearchResponseError::error_type)
+    }
+
+    /// elastic search's explanation of the error
+    pub fn reason(&self) -> Option<&str> {
+        self.elastic_search_error()
+            .and_then(ElasticSearchResponseError::reason)
+    }
+
+    /// the errors that started it all, empty when elastic search didn't say
+    pub fn root_cause(&self) -> &[ElasticSearchResponseError] {
+        self.elastic_search_error()
+            .map(ElasticSearchResponseError::root_cause)
+            .unwrap_or_default()
+    }
+
+    /// whether sending the same request again later might work
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            NixSearchError::RateLimited { .. }
+                | NixSearchError::Timeout { .. }
+                | NixSearchError::ShardFailure { .. }
+                | NixSearchError::ServerError {
+                    status: 502 | 503,
+                    ..
+                }
+        )
+    }
+}
+
 /// **USE THIS**: This is where you define what parameterizes your search  
 /// note: multiple filters are allowed.
-
+///
+/// Made with [`Query::builder`], so a new filter doesn't break anyone.
+/// The fields can still be read, and changed on a built query.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct Query {
     pub max_results: u32,
+    /// how many results to skip, for offset pagination
+    pub offset: u32,
     pub search_within: SearchWithin,
 
     pub search: Option<MatchSearch>,
@@ -147,67 +346,147 @@ pub struct Query {
     pub name: Option<MatchName>,
     pub version: Option<MatchVersion>,
     pub query_string: Option<MatchQueryString>,
+    /// extra clauses that all have to match, for anything
+    /// the fields above can't express (or, not, ...)
+    pub clauses: Vec<Clause>,
+    /// clauses that have to match without affecting the score,
+    /// like [`FilterPlatform`]
+    pub filters: Vec<Clause>,
+    /// clauses that must not match, like [`FilterAttrSet`] to leave out a package set
+    pub exclusions: Vec<Clause>,
 }
 
 impl Query {
-    fn prefix() -> Url {
-        Url::parse("https://nixos-search-7-1733963800.us-east-1.bonsaisearch.net:443/").unwrap()
+    /// Start building a query without spelling out every filter.
+    ///
+    /// ```rust
+    /// use nix_elastic_search::Query;
+    ///
+    /// let query = Query::builder()
+    ///     .channel("23.11")
+    ///     .name("rust")
+    ///     .limit(20)
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(query.max_results, 20);
+    /// ```
+    pub fn builder() -> QueryBuilder {
+        QueryBuilder::new()
     }
-    const ELASTIC_PREFIX: &'static str = "latest-*-";
-    const USERNAME: &'static str = "aWVSALXpZv";
-    const PASSWORD: &'static str = "X8gPHnzL52wFEekuxsfQ9cSh";
 
-    fn get_url(&self) -> Result<Url, url::ParseError> {
-        match &self.search_within {
-            SearchWithin::Channel(channel) => {
-                Self::prefix().join(&format!("/{}nixos-{channel}/", Self::ELASTIC_PREFIX))?
-            }
-            SearchWithin::Flakes => {
-                Self::prefix().join(&format!("{}group-manual/", Self::ELASTIC_PREFIX))?
-            }
-        }
-        .join("_search")
+    /// the url this query is sent to by the default [`Client`]
+    fn get_url(&self) -> Result<Url, NixSearchError> {
+        Client::shared().search_url(&self.search_within)
     }
 
-    /// Search nix packages for your query
+    /// Search nix packages for your query, through the default [`Client`].
+    ///
+    /// To use another cluster, credentials or timeout use [`Client::send`].
     pub fn send(&self) -> Result<Vec<NixPackage>, NixSearchError> {
-        let res = ureq::post(
-            self.get_url()
-                // we could handle this, but really I think it's such a pain
-                // and we can really verify it pretty decently anyways at compile time.
-                .expect("failed to construct url; this indicates a bug in nix-elastic-search")
-                .as_str(),
-        )
-        // gotta do the simple http authentication
-        // since the library doesn't do it for me.
-        .set(
-            "Authorization",
-            &BASE64_STANDARD.encode(format!("{}:{}", Self::USERNAME, Self::PASSWORD)),
-        )
-        .set("Content-Type", "application/json")
-        .set("Accept", "application/json")
-        .send_string(&self.payload().to_string())?;
-
-        let text = res.into_string().unwrap();
-
-        let read = match serde_json::from_str::<response::SearchResponse>(&text) {
-            Ok(r) => r,
-            Err(err) => {
-                return Err(NixSearchError::DeserializationError {
-                    path: SerdeNixPackagePath::new(text),
-                    source: err,
-                })
-            }
-        };
+        Client::shared().send(self)
+    }
 
-        match read {
-            response::SearchResponse::Error { error, status } => {
-                Err(NixSearchError::ElasticSearchError { error, status })
-            }
-            response::SearchResponse::Success { packages } => Ok(packages),
-        }
+    /// Search nix packages for your query, sending the request
+    /// through `transport` instead of the default [`UreqTransport`].
+    pub fn send_with(&self, transport: &dyn Transport) -> Result<Vec<NixPackage>, NixSearchError> {
+        Client::shared().send_with(transport, self)
+    }
+
+    /// [`Query::send`], but keeping the total hit count, the query
+    /// time, the shards the search failed on (see [`SearchResults::is_partial`]),
+    /// and the score, index and matched queries of every hit.
+    ///
+    /// The total is what elastic search counted. With a [`VersionReq`] the hits
+    /// that don't meet it are dropped after that, so the total can be higher
+    /// than every hit there is, and a page can be short.
+    ///
+    /// ```rust,no_run
+    /// use nix_elastic_search::Query;
+    ///
+    /// let query = Query::builder().channel("23.11").search("python").limit(20).build().unwrap();
+    /// let results = query.send_results().unwrap();
+    ///
+    /// println!("showing {} of {}", results.hits.len(), results.total.value);
+    /// ```
+    pub fn send_results(&self) -> Result<SearchResults, NixSearchError> {
+        Client::shared().send_results(self)
+    }
+
+    /// [`Query::send_results`], sending the request through `transport`.
+    pub fn send_results_with(
+        &self,
+        transport: &dyn Transport,
+    ) -> Result<SearchResults, NixSearchError> {
+        Client::shared().send_results_with(transport, self)
+    }
+
+    /// [`Query::send_results`], but a hit that doesn't deserialize
+    /// is skipped instead of failing the whole search.
+    ///
+    /// The skipped hits come back with their `_id` and where they went wrong,
+    /// which keeps a search working when the search.nixos.org importer
+    /// changes the shape of a field.
+    ///
+    /// ```rust,no_run
+    /// use nix_elastic_search::Query;
+    ///
+    /// let query = Query::builder().channel("23.11").search("python").build().unwrap();
+    /// let lenient = query.send_lenient().unwrap();
+    ///
+    /// for skipped in &lenient.skipped {
+    ///     eprintln!("skipped {skipped}");
+    /// }
+    /// let packages = lenient.into_documents();
+    /// ```
+    pub fn send_lenient(&self) -> Result<LenientSearchResults, NixSearchError> {
+        Client::shared().send_lenient(self)
+    }
+
+    /// [`Query::send_lenient`], sending the request through `transport`.
+    pub fn send_lenient_with(
+        &self,
+        transport: &dyn Transport,
+    ) -> Result<LenientSearchResults, NixSearchError> {
+        Client::shared().send_lenient_with(transport, self)
+    }
+
+    /// Lazily walk every package matching this query, a page
+    /// of [`Query::max_results`] packages at a time.
+    ///
+    /// The first page starts at [`Query::offset`], every page after
+    /// that continues from the sort values of the last hit (`search_after`),
+    /// so this isn't bound by the elastic search result window.
+    ///
+    /// ```rust,no_run
+    /// use nix_elastic_search::Query;
+    ///
+    /// let query = Query::builder().channel("23.11").program("python").build().unwrap();
+    ///
+    /// for package in query.iter().take(120) {
+    ///     println!("{}", package.unwrap().package_attr_name);
+    /// }
+    /// ```
+    pub fn iter(&self) -> SearchPages<'_> {
+        Client::shared().iter(self)
+    }
+
+    /// [`Query::iter`], sending every request through `transport`.
+    pub fn iter_with<'a>(&'a self, transport: &'a dyn Transport) -> SearchPages<'a> {
+        SearchPages::new(Client::shared(), transport, self)
     }
-    fn payload(&self) -> serde_json::Value {
+
+    pub(crate) fn payload(&self) -> serde_json::Value {
+        self.page_payload(self.offset, None)
+    }
+
+    /// the payload for one page of results, `search_after` holds
+    /// the sort values of the last hit of the previous page.
+    pub(crate) fn page_payload(
+        &self,
+        from: u32,
+        search_after: Option<&[serde_json::Value]>,
+    ) -> serde_json::Value {
         let starting_payload = json!({
            "match": {
                 "type": "package",
@@ -224,26 +503,66 @@ impl Query {
         ]
         .into_iter()
         .flatten()
+        .chain(self.clauses.iter().map(Clause::to_json))
         .collect::<Vec<_>>();
 
-        json!({
-            "from": 0,
+        // the sort is a list so that its order is guaranteed to
+        // line up with the `sort` values that `search_after` takes.
+        let mut payload = json!({
+            "from": from,
             "size": self.max_results,
-            "sort": {
-                "_score":            "desc",
-                "package_attr_name": "desc",
-                "package_pversion":  "desc",
-            },
+            "sort": [
+                { "_score":            "desc" },
+                { "package_attr_name": "desc" },
+                { "package_pversion":  "desc" },
+            ],
             "query": {
                 "bool": {
                     "must": must,
                 },
             }
-        })
+        });
+
+        // left out when empty so plain queries stay exactly what they were.
+        if !self.filters.is_empty() {
+            payload["query"]["bool"]["filter"] = self.filters.iter().map(Clause::to_json).collect();
+        }
+        if !self.exclusions.is_empty() {
+            payload["query"]["bool"]["must_not"] =
+                self.exclusions.iter().map(Clause::to_json).collect();
+        }
+
+        if let Some(search_after) = search_after {
+            payload["search_after"] = json!(search_after);
+        }
+
+        payload
+    }
+
+    /// What's done to the hits of this query once they're back: note the channel
+    /// they were found in, and drop the ones that don't meet the [`VersionReq`]s,
+    /// which elastic search can only narrow.
+    ///
+    /// [`QueryBuilder::build`] makes sure every requirement is one all hits have to meet.
+    pub(crate) fn finish_hits(&self, hits: &mut Vec<SearchHit>) {
+        let found_in = match &self.search_within {
+            SearchWithin::Channel(channel) => channel.parse::<Channel>().ok(),
+            SearchWithin::Flakes => None,
+        };
+        for hit in hits.iter_mut() {
+            hit.source.found_in = found_in;
+        }
+
+        for clause in self.clauses.iter().chain(&self.filters) {
+            for req in clause.required_versions() {
+                req.retain(hits);
+            }
+        }
     }
 }
 
 /// search by search string (like search.nixos.org -- I beleive)
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MatchSearch {
     pub search: String,
 }
@@ -300,6 +619,7 @@ impl MatchSearch {
 }
 
 /// search by name
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MatchName {
     pub name: String,
 }
@@ -329,6 +649,7 @@ impl MatchName {
 }
 
 /// search by programs
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MatchProgram {
     pub program: String,
 }
@@ -357,7 +678,10 @@ impl MatchProgram {
     }
 }
 
-/// search by versions
+/// search by versions, on a prefix of the text, so `1.2` finds `1.20` too.
+///
+/// For ranges or exact versions use a [`VersionReq`].
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MatchVersion {
     pub version: String,
 }
@@ -387,6 +711,7 @@ impl MatchVersion {
 }
 
 /// search by query string
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct MatchQueryString {
     pub query_string: String,
 }
@@ -409,6 +734,7 @@ mod test {
     fn test_search() {
         let query = Query {
             max_results: 20,
+            offset: 0,
             search_within: SearchWithin::Channel("23.11".to_owned()),
 
             search: None,
@@ -418,6 +744,9 @@ mod test {
             }),
             version: None,
             query_string: None,
+            clauses: Vec::new(),
+            filters: Vec::new(),
+            exclusions: Vec::new(),
         };
 
         let results = query.send().unwrap();
@@ -440,6 +769,7 @@ mod test {
     fn test_search_name() {
         let query = Query {
             max_results: 10,
+            offset: 0,
             search_within: SearchWithin::Channel("23.11".to_owned()),
             search: None,
             program: None,
@@ -448,6 +778,9 @@ mod test {
             }),
             version: None,
             query_string: None,
+            clauses: Vec::new(),
+            filters: Vec::new(),
+            exclusions: Vec::new(),
         };
 
         query.send().unwrap();
@@ -457,6 +790,7 @@ mod test {
     fn test_url() {
         let query = Query {
             max_results: 10,
+            offset: 0,
             search_within: SearchWithin::Channel("23.11".to_owned()),
             search: None,
             program: None,
@@ -465,9 +799,58 @@ mod test {
             }),
             version: None,
             query_string: None,
+            clauses: Vec::new(),
+            filters: Vec::new(),
+            exclusions: Vec::new(),
         };
 
         let url = query.get_url().unwrap();
         eprintln!("{}", url);
     }
+
+    #[test]
+    fn test_send_with_memory_transport() {
+        let cargo = NixPackage {
+            package_attr_name: "cargo".to_owned(),
+            package_pname: "cargo".to_owned(),
+            package_pversion: "1.73.0".to_owned(),
+            package_programs: vec!["cargo".to_owned()],
+            type_field: "package".to_owned(),
+            ..Default::default()
+        };
+        let transport = MemoryTransport::new();
+        transport.push_response(
+            200,
+            json!({ "hits": { "hits": [{ "_id": "cargo", "_source": cargo }] } }).to_string(),
+        );
+
+        let query = Query::builder()
+            .channel("23.11")
+            .name("cargo")
+            .build()
+            .unwrap();
+        let packages = query.send_with(&transport).unwrap();
+        assert_eq!(packages[0].package_attr_name, "cargo");
+
+        let request = &transport.requests()[0];
+        assert_eq!(request.method, Method::Post);
+        assert_eq!(request.url, query.get_url().unwrap());
+        assert_eq!(request.body.as_ref(), Some(&query.payload()));
+        assert!(request.header("authorization").is_some());
+    }
+
+    #[test]
+    fn test_send_with_error_status() {
+        let transport = MemoryTransport::new();
+        transport.push_response(
+            404,
+            r#"{"error":{"type":"index_not_found_exception","reason":"no such index","resource":{"type":"index_or_alias","id":"latest-*-nixos-1.11"}},"status":404}"#,
+        );
+
+        let query = Query::builder().channel("1.11").build().unwrap();
+        assert!(matches!(
+            query.send_with(&transport),
+            Err(NixSearchError::IndexNotFound { .. })
+        ));
+    }
 }
@@ -0,0 +1,427 @@
+//! `nix-elastic-search`, search.nixos.org from the terminal.
+//!
+//! A port of the command line of [nix-search-cli](https://github.com/peterldowns/nix-search-cli),
+//! built on this crate. Needs the `cli` feature.
+
+#![allow(clippy::result_large_err)]
+
+use std::process::ExitCode;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use nix_elastic_search::{
+    Cache, Channel, ClientBuilder, Date, FilterAttrName, Lazamar, NixPackage, NixSearchError,
+    Nixhub, Query, QueryBuilder, VersionHistory, VersionReq, VersionSource,
+};
+use serde::Serialize;
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Search nixpkgs and flakes like search.nixos.org does"
+)]
+struct Cli {
+    #[command(flatten)]
+    within: Within,
+
+    /// the maximum number of results
+    #[arg(long, short, global = true, default_value_t = 20)]
+    limit: u32,
+
+    #[command(flatten)]
+    output: Output,
+
+    /// don't read or write the response cache
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// fetch again instead of answering from the response cache
+    #[arg(long, global = true, conflicts_with = "no_cache")]
+    refresh: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Args)]
+struct Within {
+    /// the channel to search, like `23.11` or `unstable`
+    #[arg(long, short, global = true, default_value = "unstable")]
+    channel: Channel,
+
+    /// search flakes instead of a channel
+    #[arg(long, global = true, conflicts_with = "channel")]
+    flakes: bool,
+}
+
+#[derive(Debug, Args)]
+struct Output {
+    /// print json instead of a table
+    #[arg(long, global = true)]
+    json: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// search like the search.nixos.org search box
+    Search {
+        #[arg(required = true)]
+        terms: Vec<String>,
+    },
+    /// search by (the start of) the attribute name
+    Name { name: String },
+    /// search by the programs a package installs
+    Program { program: String },
+    /// list the older versions of a package, and the nixpkgs revision to get them from
+    Versions {
+        /// the package name (`package_pname`), like `ripgrep`
+        package: String,
+        /// only versions meeting a requirement, like `>=13, <14` or `=14.*`
+        req: Option<VersionReq>,
+        /// where the version history comes from
+        #[arg(long, value_enum, default_value_t = Source::Lazamar)]
+        source: Source,
+        /// only the newest revision from this day or before it, like `2023-06-01`
+        #[arg(long)]
+        as_of: Option<Date>,
+    },
+    /// show everything about one package, by its exact attribute name
+    Info { attr: String },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Source {
+    /// scraped from lazamar.co.uk/nix-versions, per channel
+    Lazamar,
+    /// the json api behind nixhub.io, unstable only
+    Nixhub,
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), NixSearchError> {
+    let mut client = ClientBuilder::from_env()?;
+    if !cli.no_cache {
+        client = client.cache(Cache::from_env().refresh(cli.refresh));
+    }
+    let client = client.build()?;
+    let query = |configure: fn(QueryBuilder, String) -> QueryBuilder, value: String| {
+        let builder = if cli.within.flakes {
+            Query::builder().flakes()
+        } else {
+            Query::builder().search_within(cli.within.channel.into())
+        };
+        configure(builder.limit(cli.limit), value).build()
+    };
+
+    match cli.command {
+        Command::Search { terms } => {
+            let packages = client.send(&query(QueryBuilder::search, terms.join(" "))?)?;
+            print_packages(&cli.output, &packages);
+            Ok(())
+        }
+        Command::Name { name } => {
+            let packages = client.send(&query(QueryBuilder::name, name)?)?;
+            print_packages(&cli.output, &packages);
+            Ok(())
+        }
+        Command::Program { program } => {
+            let packages = client.send(&query(QueryBuilder::program, program)?)?;
+            print_packages(&cli.output, &packages);
+            Ok(())
+        }
+        Command::Versions {
+            package,
+            req,
+            source,
+            as_of,
+        } => {
+            let source: Box<dyn VersionSource> = match source {
+                Source::Lazamar => Box::new(Lazamar::new(client)),
+                Source::Nixhub => Box::new(Nixhub::new(client)),
+            };
+            // only the name is known, which both sources look up by
+            let package = NixPackage {
+                package_pname: package.clone(),
+                package_attr_name: package,
+                ..Default::default()
+            };
+            let mut versions = source.package_versions(&package, cli.within.channel.into())?;
+            if let Some(req) = req {
+                versions = req.filter_versions(versions);
+            }
+            if let Some(date) = as_of {
+                versions = versions.as_of(date).into_iter().cloned().collect();
+            }
+            versions.truncate(cli.limit as usize);
+
+            if cli.output.json {
+                print_json(&versions);
+                return Ok(());
+            }
+            print_table(
+                ["VERSION", "REVISION", "DATE"],
+                versions
+                    .into_iter()
+                    .map(|v| [v.version, v.revision, v.date.to_string()]),
+            );
+            Ok(())
+        }
+        Command::Info { attr } => {
+            let exact =
+                |builder: QueryBuilder, attr: String| builder.filter(FilterAttrName(attr)).limit(1);
+            let package = client
+                .send(&query(exact, attr.clone())?)?
+                .into_iter()
+                .next()
+                .ok_or(NixSearchError::PackageNotFound { attr })?;
+
+            if cli.output.json {
+                print_json(&package);
+            } else {
+                print_info(&package);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn print_packages(output: &Output, packages: &[NixPackage]) {
+    if output.json {
+        return print_json(&packages);
+    }
+
+    print_table(
+        ["ATTRIBUTE", "VERSION", "DESCRIPTION"],
+        packages.iter().map(|package| {
+            [
+                package
+                    .flake_ref()
+                    .unwrap_or_else(|| package.package_attr_name.clone()),
+                package.package_pversion.clone(),
+                one_line(package.package_description.as_deref().unwrap_or_default()),
+            ]
+        }),
+    );
+}
+
+fn print_info(package: &NixPackage) {
+    let rows = [
+        ("attribute", package.package_attr_name.clone()),
+        ("flake", package.flake_ref().unwrap_or_default()),
+        ("name", package.package_pname.clone()),
+        ("version", package.package_pversion.clone()),
+        (
+            "description",
+            one_line(package.package_description.as_deref().unwrap_or_default()),
+        ),
+        ("homepage", package.package_homepage.join(", ")),
+        ("license", package.package_license_set.join(", ")),
+        ("maintainers", package.package_maintainers_set.join(", ")),
+        ("programs", package.package_programs.join(", ")),
+        ("outputs", package.package_outputs.join(", ")),
+        ("platforms", package.package_platforms.join(", ")),
+        (
+            "position",
+            package.package_position.clone().unwrap_or_default(),
+        ),
+    ];
+
+    for (key, value) in rows.iter().filter(|(_, value)| !value.is_empty()) {
+        println!("{:<12} {value}", format!("{key}:"));
+    }
+    if let Some(long_description) = &package.package_long_description {
+        println!("\n{}", long_description.trim());
+    }
+}
+
+fn print_json(value: &impl Serialize) {
+    // everything printed came from json in the first place
+    let json = serde_json::to_string_pretty(value).expect("results serialize to json");
+    println!("{json}");
+}
+
+fn print_table<const N: usize>(header: [&str; N], rows: impl IntoIterator<Item = [String; N]>) {
+    print!("{}", table(header, rows));
+}
+
+/// columns padded to their widest cell, the last one left as is
+fn table<const N: usize>(header: [&str; N], rows: impl IntoIterator<Item = [String; N]>) -> String {
+    let rows = rows.into_iter().collect::<Vec<_>>();
+    let mut widths = header.map(|h| h.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    let mut push_row = |cells: Vec<&str>| {
+        let mut line = String::new();
+        for (i, cell) in cells.iter().enumerate() {
+            if i + 1 == N {
+                line.push_str(cell);
+            } else {
+                line.push_str(&format!("{cell:<width$}  ", width = widths[i]));
+            }
+        }
+        table.push_str(line.trim_end());
+        table.push('\n');
+    };
+
+    push_row(header.to_vec());
+    for row in &rows {
+        push_row(row.iter().map(String::as_str).collect());
+    }
+    table
+}
+
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use clap::error::ErrorKind;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("nix-elastic-search").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_parse_subcommands() {
+        let cli = parse(&["search", "rust", "analyzer", "--channel", "nixos-23.11"]).unwrap();
+        assert!(
+            matches!(&cli.command, Command::Search { terms } if terms == &["rust", "analyzer"])
+        );
+        assert_eq!(
+            cli.within.channel,
+            Channel::Stable {
+                year: 23,
+                month: 11
+            }
+        );
+        assert_eq!(cli.limit, 20);
+
+        let cli = parse(&["--json", "program", "rg", "-l", "5"]).unwrap();
+        assert!(matches!(&cli.command, Command::Program { program } if program == "rg"));
+        assert!(cli.output.json);
+        assert_eq!(cli.limit, 5);
+
+        let cli = parse(&["name", "python3", "--flakes"]).unwrap();
+        assert!(matches!(&cli.command, Command::Name { name } if name == "python3"));
+        assert!(cli.within.flakes);
+
+        let cli = parse(&["info", "python311Packages.requests"]).unwrap();
+        assert!(
+            matches!(&cli.command, Command::Info { attr } if attr == "python311Packages.requests")
+        );
+        assert_eq!(cli.within.channel, Channel::Unstable);
+
+        let cli = parse(&[
+            "versions",
+            "ripgrep",
+            ">=13, <14",
+            "--source",
+            "nixhub",
+            "--as-of",
+            "2023-06-01",
+        ])
+        .unwrap();
+        let Command::Versions {
+            package,
+            req,
+            source,
+            as_of,
+        } = cli.command
+        else {
+            panic!("expected versions, got {:?}", cli.command);
+        };
+        assert_eq!(package, "ripgrep");
+        assert_eq!(req, Some(">=13, <14".parse().unwrap()));
+        assert!(matches!(source, Source::Nixhub));
+        assert_eq!(as_of, Some("2023-06-01".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_rejects() {
+        for (args, kind) in [
+            (&["search"][..], ErrorKind::MissingRequiredArgument),
+            (&["name", "rust", "--table"], ErrorKind::UnknownArgument),
+            (
+                &["name", "rust", "--no-cache", "--refresh"],
+                ErrorKind::ArgumentConflict,
+            ),
+            (
+                &["name", "rust", "--flakes", "--channel", "23.11"],
+                ErrorKind::ArgumentConflict,
+            ),
+            (
+                &["name", "rust", "--channel", "23.13"],
+                ErrorKind::ValueValidation,
+            ),
+            (&["versions", "ripgrep", ">="], ErrorKind::ValueValidation),
+            (
+                &["versions", "ripgrep", "--as-of", "2023-02-29"],
+                ErrorKind::ValueValidation,
+            ),
+            (
+                &["versions", "ripgrep", "--source", "github"],
+                ErrorKind::InvalidValue,
+            ),
+            (&[], ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand),
+        ] {
+            let err = parse(args).unwrap_err();
+            assert_eq!(err.kind(), kind, "{args:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_nixhub_only_unstable() {
+        let cli = parse(&[
+            "versions",
+            "ripgrep",
+            "--source",
+            "nixhub",
+            "--channel",
+            "23.11",
+            "--no-cache",
+        ])
+        .unwrap();
+        // refused before anything is sent
+        assert!(matches!(
+            run(cli),
+            Err(NixSearchError::UnsupportedChannel { channel, .. }) if channel == "nixos-23.11"
+        ));
+    }
+
+    #[test]
+    fn test_table() {
+        let table = table(
+            ["VERSION", "REVISION", "DATE"],
+            [
+                [
+                    "14.1.0".to_owned(),
+                    "9957cd4".to_owned(),
+                    "2024-01-09".to_owned(),
+                ],
+                ["13.0.0-rc.1".to_owned(), "abc".to_owned(), String::new()],
+            ],
+        );
+        assert_eq!(
+            table,
+            "VERSION      REVISION  DATE\n\
+             14.1.0       9957cd4   2024-01-09\n\
+             13.0.0-rc.1  abc\n"
+        );
+    }
+}
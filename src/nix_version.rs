@@ -0,0 +1,246 @@
+use std::{
+    cmp::Ordering,
+    convert::Infallible,
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::NixPackage;
+
+/// A package version, ordered the way `builtins.compareVersions` orders them.
+///
+/// The version is split into components at every `.` and `-`, and between
+/// digits and anything else. Components are compared one by one: numbers
+/// numerically, anything else as strings, with numbers above strings and
+/// `pre` below everything. A missing component is lower than a number.
+///
+/// ```rust
+/// use nix_elastic_search::NixVersion;
+///
+/// let v = |s: &str| s.parse::<NixVersion>().unwrap();
+///
+/// assert!(v("10.0") > v("9.1"));
+/// assert!(v("2.3pre1") < v("2.3"));
+/// assert!(v("2.3a") < v("2.3.1"));
+/// assert_eq!(v("1.02"), v("1.2"));
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NixVersion(String);
+
+impl NixVersion {
+    pub fn new(version: impl Into<String>) -> Self {
+        Self(version.into())
+    }
+
+    /// the version as it was written
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// the components the version is compared by, like `["2", "3", "pre", "1"]` for `2.3pre1`
+    pub fn components(&self) -> Components<'_> {
+        Components { rest: &self.0 }
+    }
+}
+
+/// The components of a [`NixVersion`], see [`NixVersion::components`].
+#[derive(Debug, Clone)]
+pub struct Components<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Components<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let is_separator = |c: char| c == '.' || c == '-';
+
+        self.rest = self.rest.trim_start_matches(is_separator);
+        let first = self.rest.chars().next()?;
+
+        let end = if first.is_ascii_digit() {
+            self.rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(self.rest.len())
+        } else {
+            self.rest
+                .find(|c: char| c.is_ascii_digit() || is_separator(c))
+                .unwrap_or(self.rest.len())
+        };
+
+        let (component, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Some(component)
+    }
+}
+
+/// a number without its leading zeros, so `007` and `7` compare (and hash) the same
+fn number(component: &str) -> Option<&str> {
+    if component.is_empty() || !component.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let trimmed = component.trim_start_matches('0');
+    Some(if trimmed.is_empty() { "0" } else { trimmed })
+}
+
+/// `componentsLT` from nix' `names.cc`
+fn component_lt(c1: &str, c2: &str) -> bool {
+    match (number(c1), number(c2)) {
+        // compared as digits, so they can't overflow
+        (Some(n1), Some(n2)) => (n1.len(), n1) < (n2.len(), n2),
+        (None, Some(_)) if c1.is_empty() => true,
+        _ if c1 == "pre" && c2 != "pre" => true,
+        _ if c2 == "pre" => false,
+        (_, Some(_)) => true,
+        (Some(_), _) => false,
+        _ => c1 < c2,
+    }
+}
+
+impl Ord for NixVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let mut left = self.components();
+        let mut right = other.components();
+
+        loop {
+            let (c1, c2) = match (left.next(), right.next()) {
+                (None, None) => return Ordering::Equal,
+                (c1, c2) => (c1.unwrap_or_default(), c2.unwrap_or_default()),
+            };
+
+            if component_lt(c1, c2) {
+                return Ordering::Less;
+            }
+            if component_lt(c2, c1) {
+                return Ordering::Greater;
+            }
+        }
+    }
+}
+
+impl PartialOrd for NixVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for NixVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for NixVersion {}
+
+impl Hash for NixVersion {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for component in self.components() {
+            number(component).unwrap_or(component).hash(state);
+        }
+    }
+}
+
+impl fmt::Display for NixVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for NixVersion {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::new(s))
+    }
+}
+
+impl From<&str> for NixVersion {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<String> for NixVersion {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl NixPackage {
+    /// [`NixPackage::package_pversion`], ordered the way nix orders versions
+    pub fn nix_version(&self) -> NixVersion {
+        NixVersion::new(self.package_pversion.as_str())
+    }
+}
+
+#[cfg(feature = "version-search")]
+impl crate::PackageVersion {
+    /// [`PackageVersion::version`](crate::PackageVersion::version),
+    /// ordered the way nix orders versions
+    pub fn nix_version(&self) -> NixVersion {
+        NixVersion::new(self.version.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn v(version: &str) -> NixVersion {
+        NixVersion::new(version)
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        // the examples from the `builtins.compareVersions` documentation and nix' tests
+        let ordered = [
+            ("1.0", "2.3"),
+            ("2.1", "2.3"),
+            ("2.3", "2.5"),
+            ("2.3", "3.1"),
+            ("2.3", "2.3.1"),
+            ("2.3a", "2.3.1"),
+            ("2.3pre1", "2.3"),
+            ("2.3pre3", "2.3pre12"),
+            ("2.3a", "2.3c"),
+            ("2.3pre1", "2.3c"),
+            ("2.3pre1", "2.3q"),
+            ("9.1", "10.0"),
+            ("1.0", "1.0a"),
+            ("unstable-2023-01-01", "unstable-2023-02-01"),
+            ("99999999999999999999999", "100000000000000000000000"),
+        ];
+
+        for (lower, higher) in ordered {
+            assert!(v(lower) < v(higher), "{lower} < {higher}");
+            assert!(v(higher) > v(lower), "{higher} > {lower}");
+        }
+
+        assert_eq!(v("2.3"), v("2.3"));
+        assert_eq!(v("1.02"), v("1.2"));
+        assert_eq!(v("1.0-"), v("1.0"));
+        assert_ne!(v("1"), v("1.0"));
+    }
+
+    #[test]
+    fn test_components() {
+        assert_eq!(
+            v("2.3pre1-rc.x").components().collect::<Vec<_>>(),
+            ["2", "3", "pre", "1", "rc", "x"]
+        );
+    }
+
+    #[test]
+    fn test_sort_packages() {
+        let mut versions = ["10.0", "9.1", "9.1pre", "10.0.1"].map(v);
+        versions.sort();
+        assert_eq!(
+            versions.map(|v| v.to_string()),
+            ["9.1pre", "9.1", "10.0", "10.0.1"]
+        );
+    }
+}
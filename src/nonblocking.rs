@@ -0,0 +1,252 @@
+use serde::de::DeserializeOwned;
+
+use crate::{
+    response, Client, LenientSearchResults, Method, NixOption, NixPackage, NixSearchError,
+    OptionQuery, Query, SearchResults, SearchWithin, Transport, TransportRequest,
+    TransportResponse,
+};
+
+impl Query {
+    /// Search nix packages for your query, without blocking.
+    ///
+    /// This builds the same request as [`Query::send`] and
+    /// parses the response the same way, so it fails with the same
+    /// [`NixSearchError`] variants; only connection failures come back
+    /// as [`NixSearchError::ReqwestError`] instead of [`NixSearchError::UreqError`].
+    ///
+    /// A client with a custom [`ClientBuilder::transport`](crate::ClientBuilder::transport)
+    /// sends through it instead of reqwest.
+    ///
+    /// ```rust,no_run
+    /// # async fn search() -> Result<(), nix_elastic_search::NixSearchError> {
+    /// use nix_elastic_search::Query;
+    ///
+    /// let query = Query::builder().channel("23.11").name("rust").build()?;
+    /// let packages = query.send_async().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_async(&self) -> Result<Vec<NixPackage>, NixSearchError> {
+        Client::shared().send_async(self).await
+    }
+
+    /// [`Query::send_results`], without blocking.
+    pub async fn send_results_async(&self) -> Result<SearchResults, NixSearchError> {
+        Client::shared().send_results_async(self).await
+    }
+
+    /// [`Query::send_lenient`], without blocking.
+    pub async fn send_lenient_async(&self) -> Result<LenientSearchResults, NixSearchError> {
+        Client::shared().send_lenient_async(self).await
+    }
+}
+
+impl OptionQuery {
+    /// Search NixOS options for your query, without blocking.
+    pub async fn send_async(&self) -> Result<Vec<NixOption>, NixSearchError> {
+        Client::shared().send_options_async(self).await
+    }
+}
+
+impl Client {
+    /// Search NixOS options for `query`, without blocking.
+    pub async fn send_options_async(
+        &self,
+        query: &OptionQuery,
+    ) -> Result<Vec<NixOption>, NixSearchError> {
+        let results = self
+            .search_async(&query.search_within, &query.payload())
+            .await?;
+        Ok(results.into_documents())
+    }
+
+    /// Search nix packages for `query`, without blocking, see [`Query::send_async`].
+    pub async fn send_async(&self, query: &Query) -> Result<Vec<NixPackage>, NixSearchError> {
+        Ok(self.send_results_async(query).await?.into_documents())
+    }
+
+    /// [`Client::send_results`], without blocking.
+    pub async fn send_results_async(&self, query: &Query) -> Result<SearchResults, NixSearchError> {
+        let mut results = self
+            .search_async(&query.search_within, &query.payload())
+            .await?;
+        query.finish_hits(&mut results.hits);
+        Ok(results)
+    }
+
+    /// [`Client::send_lenient`], without blocking.
+    pub async fn send_lenient_async(
+        &self,
+        query: &Query,
+    ) -> Result<LenientSearchResults, NixSearchError> {
+        let results = self
+            .search_async::<serde_json::Value>(&query.search_within, &query.payload())
+            .await?;
+        let mut lenient = results.into_lenient();
+        query.finish_hits(&mut lenient.results.hits);
+        Ok(lenient)
+    }
+
+    pub(crate) async fn search_async<T: DeserializeOwned>(
+        &self,
+        search_within: &SearchWithin,
+        payload: &serde_json::Value,
+    ) -> Result<SearchResults<T>, NixSearchError> {
+        let request = self.request(
+            Method::Post,
+            self.search_url(search_within)?,
+            Some(payload.clone()),
+        );
+
+        let res = self.send_request_async(&request).await?;
+
+        response::parse_search_response(res.status, res.body)
+    }
+
+    /// [`Transport::send`](crate::Transport::send), without blocking: through reqwest
+    /// and the cache, or through the custom transport when there is one.
+    pub(crate) async fn send_request_async(
+        &self,
+        request: &TransportRequest,
+    ) -> Result<TransportResponse, NixSearchError> {
+        let Some(client) = &self.async_client else {
+            return self.transport.send(request);
+        };
+        if let Some(response) = self.cache.as_ref().and_then(|cache| cache.cached(request)) {
+            return Ok(response);
+        }
+
+        let method = match request.method {
+            Method::Get => reqwest::Method::GET,
+            Method::Post => reqwest::Method::POST,
+        };
+        let mut builder = client.request(method, request.url.clone());
+        for (key, value) in &request.headers {
+            builder = builder.header(key, value);
+        }
+        if let Some(body) = &request.body {
+            builder = builder.body(body.to_string());
+        }
+
+        // unlike ureq, reqwest doesn't turn error statuses into errors,
+        // so elastic search's explanation ends up in the body either way.
+        let res = builder.send().await.map_err(reqwest_error)?;
+        let status = res.status().as_u16();
+        let body = res.text().await.map_err(reqwest_error)?;
+
+        let response = TransportResponse { status, body };
+        if let Some(cache) = &self.cache {
+            cache.keep(request, &response);
+        }
+        Ok(response)
+    }
+}
+
+fn reqwest_error(source: reqwest::Error) -> NixSearchError {
+    if source.is_timeout() {
+        NixSearchError::Timeout { error: None }
+    } else {
+        NixSearchError::ReqwestError { source }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fmt::Debug,
+        future::Future,
+        pin::pin,
+        sync::Arc,
+        task::{Context, Poll, Wake, Waker},
+    };
+
+    use super::*;
+    use crate::MemoryTransport;
+
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// poll `future` once, enough when it only goes through a `MemoryTransport`
+    fn ready<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(NoopWaker));
+        match pin!(future).poll(&mut Context::from_waker(&waker)) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("the future waited on something"),
+        }
+    }
+
+    /// what came back, so the blocking and async results can be compared
+    fn outcome<T: Debug>(result: &Result<T, NixSearchError>) -> String {
+        match result {
+            Ok(value) => format!("Ok({value:?})"),
+            Err(err) => format!("Err({err:?})"),
+        }
+    }
+
+    /// a client that answers every request twice with `status` and `body`
+    fn client(status: u16, body: &str) -> (Client, Arc<MemoryTransport>) {
+        let transport = Arc::new(MemoryTransport::new());
+        transport.push_response(status, body);
+        transport.push_response(status, body);
+        let client = Client::builder()
+            .user_agent("our-tooling/1.0")
+            .transport(transport.clone())
+            .build()
+            .unwrap();
+        (client, transport)
+    }
+
+    #[test]
+    fn test_search_async_fails_like_blocking() {
+        let not_found = serde_json::json!({
+            "error": { "type": "index_not_found_exception", "reason": "no such index [nixos-23.13]" },
+            "status": 404,
+        });
+        let query = Query::builder()
+            .channel("23.11")
+            .name("rust")
+            .build()
+            .unwrap();
+
+        for (status, body) in [
+            (404, not_found.to_string()),
+            (503, "Service Unavailable".to_owned()),
+            (200, r#"{ "hits": { "hits": [] } }"#.to_owned()),
+        ] {
+            let (client, transport) = client(status, &body);
+            let blocking = client.send(&query);
+            let nonblocking = ready(client.send_async(&query));
+            assert_eq!(outcome(&blocking), outcome(&nonblocking), "{status}");
+
+            let requests = transport.requests();
+            assert_eq!(requests[0], requests[1]);
+        }
+    }
+
+    #[cfg(feature = "version-search")]
+    #[test]
+    fn test_versions_async_fails_like_blocking() {
+        use crate::VersionChannel;
+
+        let no_results = include_str!("../tests/fixtures/lazamar/not_found.html");
+        for (status, body) in [
+            (404, "Not Found"),
+            (503, "down for maintenance"),
+            (200, no_results),
+        ] {
+            let (client, transport) = client(status, body);
+            let blocking = client.lookup_package_versions("rip-grep", VersionChannel::default());
+            let nonblocking =
+                ready(client.lookup_package_versions_async("rip-grep", VersionChannel::default()));
+            assert!(blocking.is_err(), "{status}");
+            assert_eq!(outcome(&blocking), outcome(&nonblocking), "{status}");
+
+            let requests = transport.requests();
+            assert_eq!(requests[0], requests[1]);
+            assert_eq!(requests[1].header("User-Agent"), Some("our-tooling/1.0"));
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use serde_json::json;
+
+use crate::{
+    Client, NixOption, NixSearchError, OptionQueryBuilder, SearchResults, SearchWithin, Transport,
+};
+
+/// Search NixOS options instead of packages,
+/// the "Options" tab of search.nixos.org.
+///
+/// ```rust,no_run
+/// use nix_elastic_search::OptionQuery;
+///
+/// let query = OptionQuery::builder()
+///     .channel("23.11")
+///     .search("nginx virtualHosts")
+///     .build()
+///     .unwrap();
+///
+/// for option in query.send().unwrap() {
+///     println!("{}: {}", option.option_name, option.option_type.unwrap_or_default());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct OptionQuery {
+    pub max_results: u32,
+    /// how many results to skip, for offset pagination
+    pub offset: u32,
+    pub search_within: SearchWithin,
+
+    pub search: Option<MatchOptionSearch>,
+    pub name: Option<MatchOptionName>,
+}
+
+impl OptionQuery {
+    pub fn builder() -> OptionQueryBuilder {
+        OptionQueryBuilder::new()
+    }
+
+    /// Search NixOS options for your query, through the default [`Client`].
+    pub fn send(&self) -> Result<Vec<NixOption>, NixSearchError> {
+        Client::shared().send_options(self)
+    }
+
+    /// Search NixOS options for your query, sending the request
+    /// through `transport` instead of the default [`UreqTransport`](crate::UreqTransport).
+    pub fn send_with(&self, transport: &dyn Transport) -> Result<Vec<NixOption>, NixSearchError> {
+        Client::shared().send_options_with(transport, self)
+    }
+
+    /// [`OptionQuery::send`], keeping the total hit count,
+    /// the query time and the score of every hit.
+    pub fn send_results(&self) -> Result<SearchResults<NixOption>, NixSearchError> {
+        Client::shared().send_option_results(self)
+    }
+
+    pub(crate) fn payload(&self) -> serde_json::Value {
+        let must = [
+            Some(json!({
+               "match": {
+                    "type": "option",
+                }
+            })),
+            self.search.as_ref().map(MatchOptionSearch::to_json),
+            self.name.as_ref().map(MatchOptionName::to_json),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+        json!({
+            "from": self.offset,
+            "size": self.max_results,
+            "sort": [
+                { "_score":      "desc" },
+                { "option_name": "desc" },
+            ],
+            "query": {
+                "bool": {
+                    "must": must,
+                },
+            }
+        })
+    }
+}
+
+impl Client {
+    /// Search NixOS options for `query`
+    pub fn send_options(&self, query: &OptionQuery) -> Result<Vec<NixOption>, NixSearchError> {
+        self.send_options_with(&*self.transport, query)
+    }
+
+    /// Search NixOS options for `query`, keeping the hit metadata.
+    pub fn send_option_results(
+        &self,
+        query: &OptionQuery,
+    ) -> Result<SearchResults<NixOption>, NixSearchError> {
+        self.search(&*self.transport, &query.search_within, &query.payload())
+    }
+
+    pub(crate) fn send_options_with(
+        &self,
+        transport: &dyn Transport,
+        query: &OptionQuery,
+    ) -> Result<Vec<NixOption>, NixSearchError> {
+        let results = self.search(transport, &query.search_within, &query.payload())?;
+        Ok(results.into_documents())
+    }
+}
+
+/// search options by search string (like search.nixos.org)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchOptionSearch {
+    pub search: String,
+}
+
+impl MatchOptionSearch {
+    pub fn to_json(&self) -> serde_json::Value {
+        let multi_match_name = format!("multi_match_{}", self.search.replace(' ', "_"));
+        let initial_query = json!({
+                "multi_match": {
+                    "type":  "cross_fields",
+                    "_name": multi_match_name,
+                    "query": self.search,
+                    "fields": [
+                        "option_name^6",
+                        "option_name.*^3.5999999999999996",
+                        "option_description^1",
+                        "option_description.*^0.6",
+                        "flake_name^0.5",
+                        "flake_name.*^0.3",
+                    ]
+            }
+        });
+
+        let queries = std::iter::once(initial_query)
+            .chain(self.search.split(' ').map(|split| {
+                json!( {
+                        "wildcard": {
+                            "option_name": {
+                                "value": format!("*{}*", split),
+                                "case_insensitive": true,
+                            },
+                        }
+                    }
+                )
+            }))
+            .collect::<Vec<_>>();
+
+        json!({
+            "dis_max":  {
+                "tie_breaker": 0.7,
+                "queries": queries,
+            }
+        })
+    }
+}
+
+/// search options by (the start of) their name, like `services.nginx`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchOptionName {
+    pub name: String,
+}
+
+impl MatchOptionName {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "wildcard": {
+                "option_name": {
+                    "value": format!("{}*", self.name),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MemoryTransport;
+
+    #[test]
+    fn test_option_search() {
+        let transport = MemoryTransport::new();
+        transport.push_response(
+            200,
+            json!({
+                "hits": {
+                    "hits": [{
+                        "_id": "services.nginx.enable",
+                        "_source": {
+                            "option_name": "services.nginx.enable",
+                            "option_description": "Whether to enable Nginx Web Server.",
+                            "option_type": "boolean",
+                            "option_default": "false",
+                            "option_example": "true",
+                            "option_source": "nixos/modules/services/web-servers/nginx/default.nix",
+                            "option_flake": null,
+                            "type": "option",
+                        },
+                    }]
+                }
+            })
+            .to_string(),
+        );
+
+        let query = OptionQuery::builder()
+            .channel("23.11")
+            .name("services.nginx")
+            .limit(5)
+            .build()
+            .unwrap();
+        let options = query.send_with(&transport).unwrap();
+
+        assert_eq!(options[0].option_name, "services.nginx.enable");
+        assert_eq!(options[0].option_type.as_deref(), Some("boolean"));
+
+        let request = &transport.requests()[0];
+        let body = request.body.as_ref().unwrap();
+        assert_eq!(body["size"], 5);
+        assert_eq!(
+            body["query"]["bool"]["must"][0],
+            json!({ "match": { "type": "option" } })
+        );
+        assert_eq!(
+            body["query"]["bool"]["must"][1]["wildcard"]["option_name"]["value"],
+            "services.nginx*"
+        );
+    }
+}
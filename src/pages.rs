@@ -0,0 +1,250 @@
+use crate::{Client, NixPackage, NixSearchError, Query, SearchHit, Transport};
+
+/// A lazy iterator over every package matching a [`Query`],
+/// see [`Query::iter`].
+///
+/// A new page is only fetched once the previous one has been used up.
+/// After an error the iterator is finished.
+pub struct SearchPages<'a> {
+    client: &'a Client,
+    query: &'a Query,
+    transport: &'a dyn Transport,
+    buffer: std::vec::IntoIter<NixPackage>,
+    search_after: Option<Vec<serde_json::Value>>,
+    done: bool,
+}
+
+impl<'a> SearchPages<'a> {
+    pub(crate) fn new(client: &'a Client, transport: &'a dyn Transport, query: &'a Query) -> Self {
+        Self {
+            client,
+            query,
+            transport,
+            buffer: Vec::new().into_iter(),
+            search_after: None,
+            done: false,
+        }
+    }
+
+    /// the payload for the page after the ones already fetched
+    fn next_payload(&self) -> serde_json::Value {
+        match &self.search_after {
+            // elastic search requires `from` to be zero when
+            // `search_after` is used, the offset is already in the sort values.
+            Some(search_after) => self.query.page_payload(0, Some(search_after)),
+            None => self.query.page_payload(self.query.offset, None),
+        }
+    }
+
+    fn fetch_page(&mut self) -> Result<(), NixSearchError> {
+        let results = self.client.search(
+            self.transport,
+            &self.query.search_within,
+            &self.next_payload(),
+        )?;
+        self.load_page(results.hits);
+        Ok(())
+    }
+
+    fn load_page(&mut self, mut hits: Vec<SearchHit>) {
+        // a short page means there's nothing after it.
+        if hits.len() < self.query.max_results as usize {
+            self.done = true;
+        }
+
+        match hits.last() {
+            Some(last) if !last.sort.is_empty() => self.search_after = Some(last.sort.clone()),
+            // without sort values there is no way to continue.
+            _ => self.done = true,
+        }
+
+        // after the page is measured, a page can be short (or empty) because of this alone.
+        self.query.finish_hits(&mut hits);
+        self.buffer = hits
+            .into_iter()
+            .map(|hit| hit.source)
+            .collect::<Vec<_>>()
+            .into_iter();
+    }
+}
+
+impl Iterator for SearchPages<'_> {
+    type Item = Result<NixPackage, NixSearchError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(package) = self.buffer.next() {
+                return Some(Ok(package));
+            }
+
+            if self.done {
+                return None;
+            }
+
+            if let Err(err) = self.fetch_page() {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    fn hit(attr: &str, sort: serde_json::Value) -> SearchHit {
+        let package = NixPackage {
+            package_attr_name: attr.to_owned(),
+            package_pname: attr.to_owned(),
+            package_pversion: "1.0".to_owned(),
+            type_field: "package".to_owned(),
+            ..Default::default()
+        };
+        serde_json::from_value(json!({ "_id": attr, "_source": package, "sort": sort })).unwrap()
+    }
+
+    #[test]
+    fn test_next_page_uses_search_after() {
+        let query = Query::builder()
+            .channel("23.11")
+            .name("rust")
+            .limit(2)
+            .offset(4)
+            .build()
+            .unwrap();
+
+        let mut pages = query.iter();
+        let first = pages.next_payload();
+        assert_eq!(first["from"], 4);
+        assert!(first.get("search_after").is_none());
+
+        pages.load_page(vec![
+            hit("rustc", json!([12.5, "rustc", "1.73.0"])),
+            hit("rustup", json!([11.0, "rustup", "1.26.0"])),
+        ]);
+        assert!(!pages.done);
+
+        let second = pages.next_payload();
+        assert_eq!(second["from"], 0);
+        assert_eq!(second["search_after"], json!([11.0, "rustup", "1.26.0"]));
+
+        let attrs = pages
+            .by_ref()
+            .take(2)
+            .map(|p| p.unwrap().package_attr_name)
+            .collect::<Vec<_>>();
+        assert_eq!(attrs, ["rustc", "rustup"]);
+    }
+
+    #[test]
+    fn test_short_page_ends_iteration() {
+        let query = Query::builder()
+            .channel("23.11")
+            .name("rust")
+            .limit(5)
+            .build()
+            .unwrap();
+
+        let mut pages = query.iter();
+        pages.load_page(vec![hit("rustc", json!([12.5, "rustc", "1.73.0"]))]);
+
+        assert_eq!(pages.next().unwrap().unwrap().package_attr_name, "rustc");
+        assert!(pages.next().is_none());
+    }
+
+    #[test]
+    fn test_iter_over_transport() {
+        let query = Query::builder()
+            .channel("23.11")
+            .name("rust")
+            .limit(2)
+            .build()
+            .unwrap();
+
+        let page = |hits: Vec<SearchHit>| json!({ "hits": { "hits": hits } }).to_string();
+
+        let transport = crate::MemoryTransport::new();
+        transport.push_response(
+            200,
+            page(vec![
+                hit("rustc", json!([12.5, "rustc", "1.73.0"])),
+                hit("rustup", json!([11.0, "rustup", "1.26.0"])),
+            ]),
+        );
+        transport.push_response(
+            200,
+            page(vec![hit("rustfmt", json!([9.0, "rustfmt", "1.0"]))]),
+        );
+
+        let attrs = query
+            .iter_with(&transport)
+            .map(|p| p.unwrap().package_attr_name)
+            .collect::<Vec<_>>();
+        assert_eq!(attrs, ["rustc", "rustup", "rustfmt"]);
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[1].body.as_ref().unwrap()["search_after"],
+            json!([11.0, "rustup", "1.26.0"])
+        );
+    }
+
+    #[test]
+    fn test_iter_checks_version_reqs() {
+        let query = Query::builder()
+            .channel("23.11")
+            .name("python")
+            .version_req(">=3.11, <3.13")
+            .limit(2)
+            .build()
+            .unwrap();
+
+        let versioned = |attr: &str, version: &str, score: f64| {
+            let mut hit = hit(attr, json!([score, attr, version]));
+            hit.source.package_pversion = version.to_owned();
+            hit
+        };
+        let page = |hits: Vec<SearchHit>| json!({ "hits": { "hits": hits } }).to_string();
+
+        let transport = crate::MemoryTransport::new();
+        // nothing on the first page meets the requirement, the iterator keeps going
+        transport.push_response(
+            200,
+            page(vec![
+                versioned("python310", "3.10.13", 12.0),
+                versioned("python313", "3.13.0", 11.0),
+            ]),
+        );
+        transport.push_response(
+            200,
+            page(vec![
+                versioned("python311", "3.11.8", 10.0),
+                versioned("python39", "3.9.18", 9.0),
+            ]),
+        );
+        transport.push_response(200, page(vec![versioned("python312", "3.12.2", 8.0)]));
+
+        let packages = query
+            .iter_with(&transport)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let attrs = packages
+            .iter()
+            .map(|p| p.package_attr_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(attrs, ["python311", "python312"]);
+        // the packages remember where they were found, for `all_versions`
+        assert_eq!(
+            packages[0].found_in,
+            Some(crate::Channel::Stable {
+                year: 23,
+                month: 11
+            })
+        );
+        assert_eq!(transport.requests().len(), 3);
+    }
+}
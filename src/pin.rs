@@ -0,0 +1,210 @@
+use crate::{NixSearchError, PackageVersion};
+
+/// The nixpkgs repository every [`PackageVersion::revision`] is a commit of.
+const NIXPKGS: &str = "github:NixOS/nixpkgs";
+
+/// References to the nixpkgs revision a [`PackageVersion`] was found in.
+///
+/// [`lookup_package_versions`](crate::lookup_package_versions) takes a `package_pname`,
+/// but everything here needs the attribute path (`package_attr_name`), like `ripgrep`
+/// or `python311Packages.requests`. The attribute is checked before it's used, and so is
+/// the revision, so neither can sneak anything into a command line or expression.
+///
+/// ```rust
+/// use nix_elastic_search::PackageVersion;
+///
+/// let version = PackageVersion {
+///     version: "14.1.0".to_owned(),
+///     revision: "9957cd48326fe8dbd52fdc50dd2502307f188b0d".to_owned(),
+///     date: "2024-01-09".parse().unwrap(),
+///     date_raw: "2024-01-09".to_owned(),
+/// };
+///
+/// assert_eq!(
+///     version.flake_ref("ripgrep").unwrap(),
+///     "github:NixOS/nixpkgs/9957cd48326fe8dbd52fdc50dd2502307f188b0d#ripgrep"
+/// );
+/// assert!(version.flake_ref("rip grep").is_err());
+/// ```
+impl PackageVersion {
+    /// a flake reference `nix run` and `nix shell` take,
+    /// like `github:NixOS/nixpkgs/<rev>#ripgrep`
+    pub fn flake_ref(&self, attr: &str) -> Result<String, NixSearchError> {
+        let attr = validate_attr_path(attr)?;
+        Ok(format!("{NIXPKGS}/{}#{attr}", self.checked_revision()?))
+    }
+
+    /// the tarball of nixpkgs at this revision,
+    /// `https://github.com/NixOS/nixpkgs/archive/<rev>.tar.gz`
+    pub fn tarball_url(&self) -> Result<String, NixSearchError> {
+        Ok(format!(
+            "https://github.com/NixOS/nixpkgs/archive/{}.tar.gz",
+            self.checked_revision()?
+        ))
+    }
+
+    /// `nix shell github:NixOS/nixpkgs/<rev>#<attr>`, needs flakes enabled
+    pub fn nix_shell_command(&self, attr: &str) -> Result<String, NixSearchError> {
+        Ok(format!("nix shell {}", self.flake_ref(attr)?))
+    }
+
+    /// `nix-shell -p <attr> -I nixpkgs=<tarball>`, for nix without flakes
+    pub fn nix_shell_legacy_command(&self, attr: &str) -> Result<String, NixSearchError> {
+        let attr = validate_attr_path(attr)?;
+        Ok(format!(
+            "nix-shell -p {attr} -I nixpkgs={}",
+            self.tarball_url()?
+        ))
+    }
+
+    /// A nix expression evaluating to the package, for a `shell.nix` or an overlay:
+    ///
+    /// ```nix
+    /// (import (builtins.fetchTarball "https://github.com/NixOS/nixpkgs/archive/<rev>.tar.gz") {}).ripgrep
+    /// ```
+    pub fn fetch_tarball_expr(&self, attr: &str) -> Result<String, NixSearchError> {
+        let attr = validate_attr_path(attr)?;
+        Ok(format!(
+            "(import (builtins.fetchTarball \"{}\") {{}}).{attr}",
+            self.tarball_url()?
+        ))
+    }
+
+    /// the revision, if it looks like a commit hash
+    fn checked_revision(&self) -> Result<&str, NixSearchError> {
+        let revision = self.revision.as_str();
+        // lazamar gives the full hash, a short one is still a valid reference.
+        let is_hash =
+            (7..=40).contains(&revision.len()) && revision.bytes().all(|b| b.is_ascii_hexdigit());
+
+        if !is_hash {
+            return Err(NixSearchError::InvalidRevision {
+                revision: revision.to_owned(),
+            });
+        }
+        Ok(revision)
+    }
+}
+
+/// Check that `attr` is a plain attribute path, dot separated nix
+/// identifiers like `python311Packages.requests`, and hand it back.
+///
+/// What passes needs no quoting, in a shell or in a nix expression.
+pub(crate) fn validate_attr_path(attr: &str) -> Result<&str, NixSearchError> {
+    let invalid = |reason: String| NixSearchError::InvalidAttrPath {
+        attr: attr.to_owned(),
+        reason,
+    };
+
+    if attr.is_empty() {
+        return Err(invalid("it is empty".to_owned()));
+    }
+
+    for name in attr.split('.') {
+        // the identifiers of nix' lexer, `[a-zA-Z_][a-zA-Z0-9_'-]*`, without the `'`: no
+        // nixpkgs attribute has one, and it would open a quote in the shell commands.
+        let mut chars = name.chars();
+        let starts_well = chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+        let continues_well = chars.all(|c| c.is_ascii_alphanumeric() || "_-".contains(c));
+
+        if !starts_well || !continues_well {
+            return Err(invalid(format!("`{name}` is not a nix identifier")));
+        }
+    }
+    Ok(attr)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn version(revision: &str) -> PackageVersion {
+        PackageVersion {
+            version: "3.31.2".to_owned(),
+            revision: revision.to_owned(),
+            date: "2024-02-01".parse().unwrap(),
+            date_raw: "2024-02-01".to_owned(),
+        }
+    }
+
+    const REV: &str = "9957cd48326fe8dbd52fdc50dd2502307f188b0d";
+
+    #[test]
+    fn test_pinned_references() {
+        let version = version(REV);
+        let attr = "python311Packages.requests";
+
+        assert_eq!(
+            version.flake_ref(attr).unwrap(),
+            format!("github:NixOS/nixpkgs/{REV}#python311Packages.requests")
+        );
+        assert_eq!(
+            version.tarball_url().unwrap(),
+            format!("https://github.com/NixOS/nixpkgs/archive/{REV}.tar.gz")
+        );
+        assert_eq!(
+            version.nix_shell_command(attr).unwrap(),
+            format!("nix shell github:NixOS/nixpkgs/{REV}#python311Packages.requests")
+        );
+        assert_eq!(
+            version.nix_shell_legacy_command(attr).unwrap(),
+            format!(
+                "nix-shell -p python311Packages.requests \
+                 -I nixpkgs=https://github.com/NixOS/nixpkgs/archive/{REV}.tar.gz"
+            )
+        );
+        assert_eq!(
+            version.fetch_tarball_expr(attr).unwrap(),
+            format!(
+                "(import (builtins.fetchTarball \
+                 \"https://github.com/NixOS/nixpkgs/archive/{REV}.tar.gz\") {{}})\
+                 .python311Packages.requests"
+            )
+        );
+    }
+
+    #[test]
+    fn test_attr_path_validation() {
+        for good in ["ripgrep", "rust-analyzer", "_7zz", "haskellPackages.lens_5"] {
+            assert_eq!(validate_attr_path(good).unwrap(), good);
+        }
+
+        for bad in [
+            "",
+            "7zip",
+            "rip grep",
+            "a..b",
+            ".a",
+            "a.",
+            "pkgs; rm -rf ~",
+            "gtk+3",
+            "a'",
+            "a' -I nixpkgs=/tmp/x",
+        ] {
+            assert!(
+                matches!(
+                    version(REV).flake_ref(bad),
+                    Err(NixSearchError::InvalidAttrPath { .. })
+                ),
+                "{bad:?} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn test_revision_validation() {
+        assert!(version("9957cd4").tarball_url().is_ok());
+
+        for bad in ["", "abc", "main", "9957cd4 && echo", &"a".repeat(41)] {
+            assert!(
+                matches!(
+                    version(bad).nix_shell_command("ripgrep"),
+                    Err(NixSearchError::InvalidRevision { .. })
+                ),
+                "{bad:?} was accepted"
+            );
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{
+    Channel, Client, FilterProgram, NixPackage, NixSearchError, Query, SearchPages, Transport,
+};
+
+/// how many packages [`find_providers`] fetches at a time
+const PAGE_SIZE: u32 = 200;
+
+/// Find every package in `channel` that installs a program named
+/// exactly `program`, the way `command-not-found` does on NixOS.
+///
+/// The most canonical attribute comes first: top level packages
+/// before ones nested in an attribute set, shallower sets before deeper ones.
+///
+/// ```rust,no_run
+/// use nix_elastic_search::{find_providers, Channel};
+///
+/// for package in find_providers("rg", Channel::Unstable).unwrap() {
+///     println!("nix shell nixpkgs#{}", package.package_attr_name);
+/// }
+/// ```
+pub fn find_providers(program: &str, channel: Channel) -> Result<Vec<NixPackage>, NixSearchError> {
+    Client::shared().find_providers(program, channel)
+}
+
+impl Client {
+    /// Find every package in `channel` that installs `program`, see [`find_providers`].
+    pub fn find_providers(
+        &self,
+        program: &str,
+        channel: Channel,
+    ) -> Result<Vec<NixPackage>, NixSearchError> {
+        self.find_providers_with(&*self.transport, program, channel)
+    }
+
+    pub(crate) fn find_providers_with(
+        &self,
+        transport: &dyn Transport,
+        program: &str,
+        channel: Channel,
+    ) -> Result<Vec<NixPackage>, NixSearchError> {
+        let query = Query::builder()
+            .search_within(channel.into())
+            .filter(FilterProgram(program.to_owned()))
+            .limit(PAGE_SIZE)
+            .build()?;
+
+        // every page, popular names like `python3` have more providers than fit in one
+        let mut providers = SearchPages::new(self, transport, &query)
+            // the index is trusted to match exactly, this makes sure of it.
+            .filter(|package| {
+                package.as_ref().map_or(true, |package| {
+                    package.package_programs.iter().any(|p| p == program)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        providers.sort_by_cached_key(provider_rank);
+
+        Ok(providers)
+    }
+}
+
+/// lower is more canonical
+fn provider_rank(package: &NixPackage) -> (bool, usize, usize, String) {
+    let attr = &package.package_attr_name;
+    let nested = !matches!(
+        package.package_attr_set.as_deref(),
+        None | Some("No package set")
+    );
+
+    (nested, attr.matches('.').count(), attr.len(), attr.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MemoryTransport;
+
+    fn hit(attr: &str, attr_set: &str, programs: &[&str]) -> serde_json::Value {
+        let package = NixPackage {
+            package_attr_name: attr.to_owned(),
+            package_attr_set: Some(attr_set.to_owned()),
+            package_programs: programs.iter().map(|p| p.to_string()).collect(),
+            type_field: "package".to_owned(),
+            ..Default::default()
+        };
+        serde_json::json!({ "_id": attr, "_source": package, "sort": [1.0, attr, "1.0"] })
+    }
+
+    #[test]
+    fn test_find_providers() {
+        let transport = MemoryTransport::new();
+        transport.push_response(
+            200,
+            serde_json::json!({
+                "hits": {
+                    "hits": [
+                        hit("python311Packages.ripgrep", "python311Packages", &["rg"]),
+                        hit("ripgrep-all", "No package set", &["rga", "rg"]),
+                        hit("rgx", "No package set", &["rgx"]),
+                        hit("ripgrep", "No package set", &["rg"]),
+                    ]
+                }
+            })
+            .to_string(),
+        );
+
+        let client = Client::default();
+        let providers = client
+            .find_providers_with(&transport, "rg", Channel::Unstable)
+            .unwrap();
+
+        assert_eq!(
+            providers
+                .iter()
+                .map(|p| p.package_attr_name.as_str())
+                .collect::<Vec<_>>(),
+            ["ripgrep", "ripgrep-all", "python311Packages.ripgrep"]
+        );
+
+        let request = &transport.requests()[0];
+        assert!(request.url.path().contains("nixos-unstable"));
+        assert_eq!(
+            request.body.as_ref().unwrap()["query"]["bool"]["filter"],
+            serde_json::json!([{ "term": { "package_programs": "rg" } }])
+        );
+    }
+
+    #[test]
+    fn test_find_providers_every_page() {
+        let transport = MemoryTransport::new();
+        let full_page = (0..PAGE_SIZE)
+            .map(|i| hit(&format!("perl5{i:03}"), "No package set", &["perl"]))
+            .collect::<Vec<_>>();
+        transport.push_response(
+            200,
+            serde_json::json!({ "hits": { "hits": full_page } }).to_string(),
+        );
+        transport.push_response(
+            200,
+            serde_json::json!({ "hits": { "hits": [hit("perl", "No package set", &["perl"])] } })
+                .to_string(),
+        );
+
+        let client = Client::default();
+        let providers = client
+            .find_providers_with(&transport, "perl", Channel::Unstable)
+            .unwrap();
+
+        assert_eq!(providers.len(), PAGE_SIZE as usize + 1);
+        assert_eq!(providers[0].package_attr_name, "perl");
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[1].body.as_ref().unwrap()["search_after"],
+            serde_json::json!([1.0, "perl5199", "1.0"])
+        );
+    }
+}
@@ -1,47 +1,240 @@
-use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    hash::{Hash, Hasher},
+    time::Duration,
+};
 
-use crate::NixSearchError;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{Channel, NixSearchError, SerdeNixPackagePath};
+
+/// Parse the body of a `_search` response, shared by
+/// the blocking and the async clients so they fail the same way.
+pub(crate) fn parse_search_response<T: DeserializeOwned>(
+    status: u16,
+    text: String,
+) -> Result<SearchResults<T>, NixSearchError> {
+    let read = match serde_json::from_str::<SearchResponse<T>>(&text) {
+        Ok(r) => r,
+        // proxies in front of the cluster answer some failures
+        // without elastic search's json, the status is all there is then.
+        Err(_) if status >= 400 => return Err(status_error(status)),
+        Err(err) => {
+            return Err(NixSearchError::DeserializationError {
+                path: SerdeNixPackagePath::for_document::<T>(text),
+                source: err,
+            });
+        }
+    };
+
+    match read {
+        SearchResponse::Error { error, status } => Err(classify_error(error, status)),
+        // any json without an `error`, like `{"message":"Forbidden"}`, reads as no hits
+        SearchResponse::Success { .. } if status >= 400 => Err(status_error(status)),
+        SearchResponse::Success { results } => Ok(results),
+    }
+}
+
+/// Turn an error elastic search explained into the most specific [`NixSearchError`].
+pub(crate) fn classify_error(error: ElasticSearchResponseError, status: i64) -> NixSearchError {
+    let has = |type_field: &str| error.types().any(|t| t == type_field);
+
+    if has("index_not_found_exception") {
+        NixSearchError::IndexNotFound { error }
+    } else if status == 401 || status == 403 || has("security_exception") {
+        NixSearchError::AuthenticationFailed {
+            status,
+            error: Some(error),
+        }
+    } else if status == 429 || has("es_rejected_execution_exception") {
+        NixSearchError::RateLimited { error: Some(error) }
+    } else if status == 408 || status == 504 || error.types().any(|t| t.contains("timeout")) {
+        NixSearchError::Timeout { error: Some(error) }
+    } else if status == 400 || MALFORMED_QUERY_TYPES.iter().any(|t| has(t)) {
+        NixSearchError::MalformedQuery { error }
+    } else if has("search_phase_execution_exception") || !error.failed_shards.is_empty() {
+        NixSearchError::ShardFailure { error, status }
+    } else {
+        NixSearchError::ElasticSearchError { error, status }
+    }
+}
+
+/// The error for a failed status when the body didn't say why.
+pub(crate) fn status_error(status: u16) -> NixSearchError {
+    let status = status.into();
+    match status {
+        401 | 403 => NixSearchError::AuthenticationFailed {
+            status,
+            error: None,
+        },
+        429 => NixSearchError::RateLimited { error: None },
+        408 | 504 => NixSearchError::Timeout { error: None },
+        500..=599 => NixSearchError::ServerError { status },
+        _ => NixSearchError::UnexpectedStatus { status },
+    }
+}
+
+/// what elastic search calls a query it couldn't make sense of
+const MALFORMED_QUERY_TYPES: &[&str] = &[
+    "parsing_exception",
+    "parse_exception",
+    "query_shard_exception",
+    "x_content_parse_exception",
+    "illegal_argument_exception",
+];
 
 // Response is the format for an ElasticSearch API response.
 // If the request was successful, only `Hits` will be populated.
 // if the request failed, `Error` and `Status` will both be set, and `Hits` will be empty.pub struct Response {
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(from = "InnerSearchResponse")]
-pub(crate) enum SearchResponse {
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(from = "InnerSearchResponse<T>")]
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+pub(crate) enum SearchResponse<T = NixPackage> {
     Error {
         error: ElasticSearchResponseError,
         status: i64,
     },
     Success {
-        packages: Vec<NixPackage>,
+        results: SearchResults<T>,
     },
 }
 
-impl From<InnerSearchResponse> for SearchResponse {
-    fn from(value: InnerSearchResponse) -> Self {
+impl<T> From<InnerSearchResponse<T>> for SearchResponse<T> {
+    fn from(value: InnerSearchResponse<T>) -> Self {
         match value.error.zip(value.status) {
             Some((error, status)) => SearchResponse::Error { error, status },
             None => SearchResponse::Success {
-                packages: value.hits.hits.into_iter().map(|h| h.source).collect(),
+                results: SearchResults {
+                    total: value.hits.total.unwrap_or(TotalHits {
+                        value: value.hits.hits.len() as u64,
+                        relation: TotalHitsRelation::Equal,
+                    }),
+                    took: Duration::from_millis(value.took.unwrap_or_default()),
+                    shards: value.shards,
+                    hits: value.hits.hits,
+                },
             },
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-struct InnerSearchResponse {
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+struct InnerSearchResponse<T> {
     error: Option<ElasticSearchResponseError>,
     status: Option<i64>,
-    hits: Hits,
+    /// milliseconds
+    took: Option<u64>,
+    #[serde(default, rename = "_shards")]
+    shards: Shards,
+    // error responses don't have any hits.
+    #[serde(default)]
+    hits: Hits<T>,
 }
 
+/// What elastic search says went wrong, the `error` of a failed response.
+///
+/// The errors in [`root_cause`](Self::root_cause) and
+/// [`caused_by`](Self::caused_by) have the same shape.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ElasticSearchResponseError {
     #[serde(rename = "type")]
     type_field: String,
-    reason: String,
-    resource: ElasticSearchResponseErrorResource,
+    #[serde(default)]
+    reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    root_cause: Vec<ElasticSearchResponseError>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    caused_by: Option<Box<ElasticSearchResponseError>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    index: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    failed_shards: Vec<ShardFailure>,
+    // elastic search flattens the resource into dotted keys,
+    // `resource.type` and `resource.id`.
+    #[serde(
+        rename = "resource.type",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    resource_type: Option<String>,
+    #[serde(
+        rename = "resource.id",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    resource_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resource: Option<ElasticSearchResponseErrorResource>,
+}
+
+impl ElasticSearchResponseError {
+    /// the kind of error, like `index_not_found_exception`
+    pub fn error_type(&self) -> &str {
+        &self.type_field
+    }
+
+    /// the human readable explanation, like `no such index [latest-42-nixos-1.11]`
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// the errors that started it all, usually more specific than this one
+    pub fn root_cause(&self) -> &[ElasticSearchResponseError] {
+        &self.root_cause
+    }
+
+    /// the error underneath this one
+    pub fn caused_by(&self) -> Option<&ElasticSearchResponseError> {
+        self.caused_by.as_deref()
+    }
+
+    /// the index the error is about, if it is about one
+    pub fn index(&self) -> Option<&str> {
+        self.index.as_deref().or_else(|| {
+            self.root_cause
+                .iter()
+                .find_map(ElasticSearchResponseError::index)
+        })
+    }
+
+    /// the shards a search failed on, for `search_phase_execution_exception`s
+    pub fn failed_shards(&self) -> &[ShardFailure] {
+        &self.failed_shards
+    }
+
+    /// the thing the error is about, like the index alias that doesn't exist
+    pub fn resource(&self) -> Option<ElasticSearchResponseErrorResource> {
+        match (&self.resource_type, &self.resource_id) {
+            (Some(type_field), Some(id)) => Some(ElasticSearchResponseErrorResource {
+                type_field: type_field.clone(),
+                id: id.clone(),
+            }),
+            _ => self.resource.clone(),
+        }
+    }
+
+    /// the type of this error and every error it explains itself with
+    fn types(&self) -> impl Iterator<Item = &str> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let error = stack.pop()?;
+            stack.extend(&error.root_cause);
+            stack.extend(error.caused_by.as_deref());
+            stack.extend(error.failed_shards.iter().filter_map(|s| s.reason.as_ref()));
+            Some(error.type_field.as_str())
+        })
+    }
+}
+
+impl std::fmt::Display for ElasticSearchResponseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.reason {
+            Some(reason) => write!(f, "{}: {}", self.type_field, reason),
+            None => write!(f, "{}", self.type_field),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -51,39 +244,244 @@ pub struct ElasticSearchResponseErrorResource {
     id: String,
 }
 
+impl ElasticSearchResponseErrorResource {
+    /// what kind of thing, like `index_or_alias`
+    pub fn resource_type(&self) -> &str {
+        &self.type_field
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
 pub struct ErrorResource {}
+
+/// A shard a search failed on.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-struct Hits {
-    pub hits: Vec<Hit>,
+pub struct ShardFailure {
+    #[serde(default)]
+    pub shard: Option<i64>,
+    #[serde(default)]
+    pub index: Option<String>,
+    #[serde(default)]
+    pub node: Option<String>,
+    #[serde(default)]
+    pub reason: Option<ElasticSearchResponseError>,
+}
+
+/// How many shards a search ran on, the `_shards` of a response.
+///
+/// A search can succeed on some shards and fail on others, the hits are
+/// then only from the shards that succeeded, see [`SearchResults::is_partial`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Shards {
+    #[serde(default)]
+    pub total: u64,
+    #[serde(default)]
+    pub successful: u64,
+    #[serde(default)]
+    pub skipped: u64,
+    #[serde(default)]
+    pub failed: u64,
+    /// why the failed shards failed
+    #[serde(default)]
+    pub failures: Vec<ShardFailure>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-struct Hit {
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+struct Hits<T> {
+    total: Option<TotalHits>,
+    pub hits: Vec<SearchHit<T>>,
+}
+
+impl<T> Default for Hits<T> {
+    fn default() -> Self {
+        Self {
+            total: None,
+            hits: Vec::new(),
+        }
+    }
+}
+
+/// One page of search results, along with what elastic
+/// search knows about the search as a whole.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResults<T = NixPackage> {
+    /// how many documents matched, not just the ones on this page
+    pub total: TotalHits,
+    /// how long elastic search took to run the query
+    pub took: Duration,
+    /// the shards the search ran on, and the ones it failed on
+    pub shards: Shards,
+    pub hits: Vec<SearchHit<T>>,
+}
+
+impl<T> SearchResults<T> {
+    /// whether the search failed on some shards, and so some hits may be missing
+    pub fn is_partial(&self) -> bool {
+        self.shards.failed > 0
+    }
+
+    /// throw away the metadata and keep the packages (or options)
+    pub fn into_documents(self) -> Vec<T> {
+        self.hits.into_iter().map(|hit| hit.source).collect()
+    }
+}
+
+impl SearchResults<serde_json::Value> {
+    /// Deserialize every hit on its own, keeping the ones that
+    /// fit `T` and setting aside the ones that don't.
+    pub(crate) fn into_lenient<T: DeserializeOwned>(self) -> LenientSearchResults<T> {
+        let mut hits = Vec::with_capacity(self.hits.len());
+        let mut skipped = Vec::new();
+
+        for hit in self.hits {
+            match serde_path_to_error::deserialize::<_, T>(hit.source) {
+                Ok(source) => hits.push(SearchHit {
+                    id: hit.id,
+                    index: hit.index,
+                    score: hit.score,
+                    source,
+                    matched_queries: hit.matched_queries,
+                    sort: hit.sort,
+                }),
+                Err(err) => skipped.push(SkippedHit {
+                    id: hit.id,
+                    path: err.path().to_string(),
+                    error: err.into_inner(),
+                }),
+            }
+        }
+
+        LenientSearchResults {
+            results: SearchResults {
+                total: self.total,
+                took: self.took,
+                shards: self.shards,
+                hits,
+            },
+            skipped,
+        }
+    }
+}
+
+/// The results of a lenient search, see [`Query::send_lenient`](crate::Query::send_lenient).
+///
+/// `results.total` still counts the skipped hits.
+#[derive(Debug)]
+pub struct LenientSearchResults<T = NixPackage> {
+    pub results: SearchResults<T>,
+    /// the hits that didn't deserialize
+    pub skipped: Vec<SkippedHit>,
+}
+
+impl<T> LenientSearchResults<T> {
+    /// throw away the metadata and keep the packages that deserialized
+    pub fn into_documents(self) -> Vec<T> {
+        self.results.into_documents()
+    }
+}
+
+/// A hit a lenient search couldn't deserialize.
+#[derive(Debug)]
+pub struct SkippedHit {
+    /// the `_id` of the hit, like `rust` for the `rust` package
+    pub id: String,
+    /// where in `_source` it went wrong, like `package_license[0]` when
+    /// a license has no `fullName`, the error says which field is missing
+    pub path: String,
+    pub error: serde_json::Error,
+}
+
+impl std::fmt::Display for SkippedHit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "hit `{}` at {}: {}", self.id, self.path, self.error)
+    }
+}
+
+/// The amount of documents a search matched.
+///
+/// Past 10,000 matches elastic search stops counting, then
+/// `relation` is [`TotalHitsRelation::GreaterThanOrEqual`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "InnerTotalHits")]
+pub struct TotalHits {
+    pub value: u64,
+    pub relation: TotalHitsRelation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TotalHitsRelation {
+    #[serde(rename = "eq")]
+    Equal,
+    #[serde(rename = "gte")]
+    GreaterThanOrEqual,
+}
+
+/// elastic search 6 sends a bare number, 7 and up an object.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum InnerTotalHits {
+    Count(u64),
+    Object {
+        value: u64,
+        relation: TotalHitsRelation,
+    },
+}
+
+impl From<InnerTotalHits> for TotalHits {
+    fn from(value: InnerTotalHits) -> Self {
+        match value {
+            InnerTotalHits::Count(value) => TotalHits {
+                value,
+                relation: TotalHitsRelation::Equal,
+            },
+            InnerTotalHits::Object { value, relation } => TotalHits { value, relation },
+        }
+    }
+}
+
+/// A single search result and how it was found.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+pub struct SearchHit<T = NixPackage> {
     #[serde(rename = "_id")]
     pub id: String,
-    // #[serde(rename = "_index")]
-    // pub index: String,
-    // #[serde(rename = "_score")]
-    // pub score: f64,
+    /// the concrete index the hit came from, like `nixos-42-23.11-...`
+    #[serde(rename = "_index", default)]
+    pub index: String,
+    /// how well the hit matched, higher is better
+    #[serde(rename = "_score", default)]
+    pub score: Option<f64>,
     #[serde(rename = "_source")]
-    pub source: NixPackage,
-    // #[serde(rename = "_type")]
-    // pub type_field: String,
-    // #[serde(default)]
-    // pub matched_queries: Vec<String>,
-    // pub sort: (f64, String, String),
+    pub source: T,
+    /// the names of the queries that matched,
+    /// like the `multi_match_<term>` that [`MatchSearch`](crate::MatchSearch) sets
+    #[serde(default)]
+    pub matched_queries: Vec<String>,
+    /// the values this hit was sorted by, what `search_after` takes
+    /// to continue from this hit.
+    #[serde(default)]
+    pub sort: Vec<serde_json::Value>,
 }
 
 /// The primary thing that describes a Nix Package.
 ///
 /// Constructed from succesful queries of the nix search elastic
 /// search api, allows for reading of package attributes.
-#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+///
+/// Fields the index has but this struct doesn't know
+/// about end up in [`extra`](Self::extra).
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct NixPackage {
     pub package_attr_name: String,
     pub package_attr_set: Option<String>,
     pub package_default_output: Option<String>,
     pub package_description: Option<String>,
+    #[serde(rename = "package_longDescription", default)]
+    pub package_long_description: Option<String>,
     pub package_homepage: Vec<String>,
     pub package_license: Vec<PackageLicense>,
     pub package_license_set: Vec<String>,
@@ -96,36 +494,521 @@ pub struct NixPackage {
     pub package_programs: Vec<String>,
     pub package_pversion: String,
     pub package_system: Option<String>,
+    /// the hydra build of the package, when the importer found one
+    #[serde(default)]
+    pub package_hydra: Option<serde_json::Value>,
+    /// the name of the flake, only set when searching flakes
+    #[serde(default)]
+    pub flake_name: Option<String>,
+    /// the description of the flake, only set when searching flakes
+    #[serde(default)]
+    pub flake_description: Option<String>,
+    /// where the flake was fetched from, only set when searching flakes
+    #[serde(default)]
+    pub flake_resolved: Option<FlakeResolved>,
     #[serde(rename = "type")]
     pub type_field: String,
+    /// the channel the package was found in, filled in by the search
+    /// (not the index), `None` for flakes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub found_in: Option<Channel>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+// `serde_json::Value` isn't `Hash`, so the extra fields are left out.
+// values that are equal still hash the same.
+impl Hash for NixPackage {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.package_attr_name.hash(state);
+        self.package_attr_set.hash(state);
+        self.package_default_output.hash(state);
+        self.package_description.hash(state);
+        self.package_long_description.hash(state);
+        self.package_homepage.hash(state);
+        self.package_license.hash(state);
+        self.package_license_set.hash(state);
+        self.package_maintainers.hash(state);
+        self.package_maintainers_set.hash(state);
+        self.package_outputs.hash(state);
+        self.package_platforms.hash(state);
+        self.package_pname.hash(state);
+        self.package_position.hash(state);
+        self.package_programs.hash(state);
+        self.package_pversion.hash(state);
+        self.package_system.hash(state);
+        self.flake_name.hash(state);
+        self.flake_description.hash(state);
+        self.flake_resolved.hash(state);
+        self.type_field.hash(state);
+        self.found_in.hash(state);
+    }
+}
+
+/// The `flake_resolved` of a package from a flake,
+/// what the flake reference resolved to.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlakeResolved {
+    /// like `github`, `gitlab`, `git` or `path`
+    #[serde(rename = "type")]
+    pub type_field: String,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub repo: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+impl Hash for FlakeResolved {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.type_field.hash(state);
+        self.owner.hash(state);
+        self.repo.hash(state);
+        self.url.hash(state);
+    }
 }
 
 #[cfg(feature = "version-search")]
 impl NixPackage {
-    /// WARNING: THIS CALLS A WEB SCRAPER. USE RESPONSIBLY.
+    /// Every version of this package `source` knows, from the channel it
+    /// was found in, like `nixos-23.11` (`nixpkgs-unstable` for a package from a flake).
+    ///
+    /// WARNING: WITH [`Lazamar`](crate::Lazamar) THIS CALLS A WEB SCRAPER. USE RESPONSIBLY.
     /// IF your'e doing a lot of searching, you should not
     /// be querying this method eagerly. It's going to be slow,
     /// and as far as I can tell, this person hosts this
     /// site out of the goodness of their heart for the Nix
     /// community to enjoy. Please have friction to using
     /// this method in your applications.
-    pub fn all_versions(&self) -> Result<Vec<crate::PackageVersion>, NixSearchError> {
-        crate::version::lookup_package_versions(&self.package_pname)
+    ///
+    /// ```rust,no_run
+    /// use nix_elastic_search::{Lazamar, Query};
+    ///
+    /// let query = Query::builder().channel("23.11").name("ripgrep").build().unwrap();
+    /// let package = &query.send().unwrap()[0];
+    ///
+    /// // the revisions of nixos-23.11 that had ripgrep
+    /// let versions = package.all_versions(&Lazamar::default()).unwrap();
+    /// ```
+    pub fn all_versions(
+        &self,
+        source: &(impl crate::VersionSource + ?Sized),
+    ) -> Result<Vec<crate::PackageVersion>, NixSearchError> {
+        let channel = self.found_in.map(Into::into).unwrap_or_default();
+        source.package_versions(self, channel)
     }
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Eq, Hash)]
+/// A NixOS option, what the "Options" tab of search.nixos.org shows.
+///
+/// Constructed from succesful [`OptionQuery`](crate::OptionQuery) searches.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NixOption {
+    pub option_name: String,
+    pub option_description: Option<String>,
+    pub option_type: Option<String>,
+    /// the default as a rendered nix expression
+    pub option_default: Option<String>,
+    /// an example as a rendered nix expression
+    pub option_example: Option<String>,
+    /// the file declaring the option, like `nixos/modules/services/web-servers/nginx/default.nix`
+    pub option_source: Option<String>,
+    /// the flake the option comes from, only set when searching flakes
+    pub option_flake: Option<serde_json::Value>,
+    #[serde(rename = "type")]
+    pub type_field: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Eq)]
 pub struct PackageLicense {
     #[serde(rename = "fullName")]
     pub full_name: String,
     pub url: Option<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+impl Hash for PackageLicense {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.full_name.hash(state);
+        self.url.hash(state);
+    }
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Eq, Hash)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Eq)]
 #[serde(default)]
 pub struct PackageMaintainer {
     pub email: Option<String>,
     pub name: Option<String>,
-    // #[serde(flatten)]
-    // pub extras: HashMap<String, serde_json::Value>,
+    /// the maintainer's github handle
+    pub github: Option<String>,
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+impl Hash for PackageMaintainer {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.email.hash(state);
+        self.name.hash(state);
+        self.github.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_error_body_without_hits() {
+        let body = r#"{
+            "error": {
+                "root_cause": [{
+                    "type": "index_not_found_exception",
+                    "reason": "no such index [latest-*-nixos-1.11]",
+                    "resource.type": "index_or_alias",
+                    "resource.id": "latest-*-nixos-1.11",
+                    "index": "latest-*-nixos-1.11"
+                }],
+                "type": "index_not_found_exception",
+                "reason": "no such index [latest-*-nixos-1.11]",
+                "resource.type": "index_or_alias",
+                "resource.id": "latest-*-nixos-1.11",
+                "index": "latest-*-nixos-1.11"
+            },
+            "status": 404
+        }"#;
+
+        let err = parse_search_response::<NixPackage>(404, body.to_owned()).unwrap_err();
+        let NixSearchError::IndexNotFound { error } = &err else {
+            panic!("expected IndexNotFound, got {err:?}");
+        };
+        assert_eq!(error.index(), Some("latest-*-nixos-1.11"));
+        assert_eq!(error.resource().unwrap().resource_type(), "index_or_alias");
+        assert_eq!(err.error_type(), Some("index_not_found_exception"));
+        assert_eq!(err.root_cause().len(), 1);
+        assert!(!err.is_retryable());
+    }
+
+    fn error_body(status: i64, error: serde_json::Value) -> String {
+        serde_json::json!({ "error": error, "status": status }).to_string()
+    }
+
+    #[test]
+    fn test_classify_errors() {
+        let malformed = error_body(
+            400,
+            serde_json::json!({
+                "root_cause": [{
+                    "type": "query_shard_exception",
+                    "reason": "Failed to parse query [rust AND]",
+                    "index": "nixos-42-23.11-7a1a6a",
+                }],
+                "type": "search_phase_execution_exception",
+                "reason": "all shards failed",
+                "phase": "query",
+                "grouped": true,
+                "failed_shards": [{
+                    "shard": 0,
+                    "index": "nixos-42-23.11-7a1a6a",
+                    "node": "abc",
+                    "reason": {
+                        "type": "query_shard_exception",
+                        "reason": "Failed to parse query [rust AND]",
+                        "caused_by": { "type": "parse_exception", "reason": "Cannot parse" },
+                    },
+                }],
+            }),
+        );
+        let err = parse_search_response::<NixPackage>(400, malformed).unwrap_err();
+        assert!(matches!(err, NixSearchError::MalformedQuery { .. }));
+        assert_eq!(err.reason(), Some("all shards failed"));
+        assert_eq!(err.root_cause()[0].error_type(), "query_shard_exception");
+
+        let shards = error_body(
+            503,
+            serde_json::json!({
+                "type": "search_phase_execution_exception",
+                "reason": "all shards failed",
+                "failed_shards": [{ "shard": 1, "reason": { "type": "node_not_connected_exception" } }],
+            }),
+        );
+        let err = parse_search_response::<NixPackage>(503, shards).unwrap_err();
+        let NixSearchError::ShardFailure { error, status: 503 } = &err else {
+            panic!("expected ShardFailure, got {err:?}");
+        };
+        assert_eq!(error.failed_shards()[0].shard, Some(1));
+        assert!(err.is_retryable());
+
+        let auth = error_body(
+            401,
+            serde_json::json!({ "type": "security_exception", "reason": "unable to authenticate user" }),
+        );
+        assert!(matches!(
+            parse_search_response::<NixPackage>(401, auth),
+            Err(NixSearchError::AuthenticationFailed {
+                status: 401,
+                error: Some(_)
+            })
+        ));
+
+        let rejected = error_body(
+            429,
+            serde_json::json!({ "type": "es_rejected_execution_exception", "reason": "rejected execution" }),
+        );
+        assert!(matches!(
+            parse_search_response::<NixPackage>(429, rejected),
+            Err(NixSearchError::RateLimited { error: Some(_) })
+        ));
+
+        let timeout = error_body(
+            504,
+            serde_json::json!({ "type": "timeout_exception", "reason": "search timed out" }),
+        );
+        assert!(matches!(
+            parse_search_response::<NixPackage>(504, timeout),
+            Err(NixSearchError::Timeout { error: Some(_) })
+        ));
+
+        let other = error_body(500, serde_json::json!({ "type": "null_pointer_exception" }));
+        assert!(matches!(
+            parse_search_response::<NixPackage>(500, other),
+            Err(NixSearchError::ElasticSearchError { status: 500, .. })
+        ));
+    }
+
+    #[test]
+    fn test_status_without_elastic_search_body() {
+        let err =
+            parse_search_response::<NixPackage>(429, "Too Many Requests".to_owned()).unwrap_err();
+        assert!(matches!(err, NixSearchError::RateLimited { error: None }));
+        assert!(err.root_cause().is_empty());
+
+        assert!(matches!(
+            parse_search_response::<NixPackage>(403, "<html>Forbidden</html>".to_owned()),
+            Err(NixSearchError::AuthenticationFailed {
+                status: 403,
+                error: None
+            })
+        ));
+        let err = parse_search_response::<NixPackage>(
+            502,
+            "<html><body><h1>502 Bad Gateway</h1></body></html>".to_owned(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, NixSearchError::ServerError { status: 502 }));
+        assert!(err.is_retryable());
+
+        assert!(matches!(
+            parse_search_response::<NixPackage>(404, "Not Found".to_owned()),
+            Err(NixSearchError::UnexpectedStatus { status: 404 })
+        ));
+    }
+
+    #[test]
+    fn test_status_with_other_json() {
+        // json from a proxy, without elastic search's `error` and `status`
+        assert!(matches!(
+            parse_search_response::<NixPackage>(403, r#"{"message":"Forbidden"}"#.to_owned()),
+            Err(NixSearchError::AuthenticationFailed {
+                status: 403,
+                error: None
+            })
+        ));
+        assert!(matches!(
+            parse_search_response::<NixPackage>(500, "{}".to_owned()),
+            Err(NixSearchError::ServerError { status: 500 })
+        ));
+
+        // the same body with a successful status is just no hits
+        let results = parse_search_response::<NixPackage>(200, "{}".to_owned()).unwrap();
+        assert!(results.hits.is_empty());
+    }
+
+    #[test]
+    fn test_hit_metadata() {
+        let body = serde_json::json!({
+            "took": 12,
+            "hits": {
+                "total": { "value": 1342, "relation": "eq" },
+                "hits": [{
+                    "_id": "option-services.nginx.enable",
+                    "_index": "nixos-42-23.11-7a1a6a",
+                    "_score": 18.5,
+                    "_source": {
+                        "option_name": "services.nginx.enable",
+                        "option_description": null,
+                        "option_type": "boolean",
+                        "option_default": "false",
+                        "option_example": null,
+                        "option_source": null,
+                        "option_flake": null,
+                        "type": "option",
+                    },
+                    "matched_queries": ["multi_match_nginx"],
+                    "sort": [18.5, "services.nginx.enable"],
+                }]
+            }
+        });
+
+        let results = parse_search_response::<NixOption>(200, body.to_string()).unwrap();
+        assert_eq!(
+            results.total,
+            TotalHits {
+                value: 1342,
+                relation: TotalHitsRelation::Equal
+            }
+        );
+        assert_eq!(results.took, Duration::from_millis(12));
+
+        let hit = &results.hits[0];
+        assert_eq!(hit.index, "nixos-42-23.11-7a1a6a");
+        assert_eq!(hit.score, Some(18.5));
+        assert_eq!(hit.matched_queries, ["multi_match_nginx"]);
+        assert_eq!(hit.source.option_name, "services.nginx.enable");
+    }
+
+    #[test]
+    fn test_partial_shard_failure() {
+        let body = include_str!("../tests/fixtures/elastic/partial_shards.json");
+        let results = parse_search_response::<serde_json::Value>(200, body.to_owned()).unwrap();
+
+        assert!(results.is_partial());
+        assert_eq!(results.shards.total, 5);
+        assert_eq!(results.shards.failed, 1);
+        let failure = &results.shards.failures[0];
+        assert_eq!(failure.shard, Some(3));
+        assert_eq!(
+            failure
+                .reason
+                .as_ref()
+                .map(ElasticSearchResponseError::error_type),
+            Some("node_not_connected_exception")
+        );
+        assert_eq!(results.hits[0].id, "ripgrep");
+
+        let body = r#"{ "_shards": { "total": 1, "successful": 1, "failed": 0 }, "hits": { "hits": [] } }"#;
+        let results = parse_search_response::<NixPackage>(200, body.to_owned()).unwrap();
+        assert!(!results.is_partial());
+    }
+
+    #[test]
+    fn test_lenient_skips_bad_hits() {
+        let package = |attr: &str, license: serde_json::Value| {
+            let mut source = serde_json::to_value(NixPackage {
+                package_attr_name: attr.to_owned(),
+                package_pname: attr.to_owned(),
+                type_field: "package".to_owned(),
+                ..Default::default()
+            })
+            .unwrap();
+            source["package_license"] = serde_json::json!([license]);
+            serde_json::json!({ "_id": attr, "_source": source })
+        };
+        let body = serde_json::json!({
+            "hits": {
+                "total": { "value": 2, "relation": "eq" },
+                "hits": [
+                    package("ripgrep", serde_json::json!({ "fullName": "MIT License" })),
+                    package("broken", serde_json::json!({ "url": "https://example.org" })),
+                ]
+            }
+        })
+        .to_string();
+
+        assert!(parse_search_response::<NixPackage>(200, body.clone()).is_err());
+
+        let lenient = parse_search_response::<serde_json::Value>(200, body)
+            .unwrap()
+            .into_lenient::<NixPackage>();
+        assert_eq!(lenient.results.total.value, 2);
+        assert_eq!(lenient.results.hits.len(), 1);
+        assert_eq!(lenient.results.hits[0].source.package_attr_name, "ripgrep");
+
+        assert_eq!(lenient.skipped.len(), 1);
+        assert_eq!(lenient.skipped[0].id, "broken");
+        assert_eq!(lenient.skipped[0].path, "package_license[0]");
+    }
+
+    #[test]
+    fn test_unknown_and_flake_fields() {
+        let package: NixPackage = serde_json::from_value(serde_json::json!({
+            "package_attr_name": "default",
+            "package_attr_set": "No package set",
+            "package_default_output": "out",
+            "package_description": "A tool",
+            "package_longDescription": "A tool that does things",
+            "package_homepage": [],
+            "package_license": [{ "fullName": "MIT License", "url": null, "spdxId": "MIT" }],
+            "package_license_set": ["MIT License"],
+            "package_maintainers": [{ "name": "Jane", "email": null, "github": "jane", "githubId": 42 }],
+            "package_maintainers_set": ["Jane"],
+            "package_outputs": ["out"],
+            "package_platforms": ["x86_64-linux"],
+            "package_pname": "tool",
+            "package_position": null,
+            "package_programs": ["tool"],
+            "package_pversion": "1.0",
+            "package_system": "x86_64-linux",
+            "package_hydra": null,
+            "flake_name": "tool",
+            "flake_description": "The tool flake",
+            "flake_resolved": { "type": "github", "owner": "jane", "repo": "tool" },
+            "flake_source": { "type": "github", "owner": "jane", "repo": "tool" },
+            "type": "package",
+        }))
+        .unwrap();
+
+        assert_eq!(
+            package.package_long_description.as_deref(),
+            Some("A tool that does things")
+        );
+        assert_eq!(package.flake_name.as_deref(), Some("tool"));
+        let resolved = package.flake_resolved.as_ref().unwrap();
+        assert_eq!(resolved.type_field, "github");
+        assert_eq!(resolved.owner.as_deref(), Some("jane"));
+        assert!(package.extra.contains_key("flake_source"));
+        assert!(!package.extra.contains_key("flake_name"));
+
+        let maintainer = &package.package_maintainers[0];
+        assert_eq!(maintainer.github.as_deref(), Some("jane"));
+        assert_eq!(maintainer.extra["githubId"], 42);
+        assert_eq!(package.package_license[0].extra["spdxId"], "MIT");
+
+        // the extra fields survive a round trip
+        let json = serde_json::to_value(&package).unwrap();
+        assert_eq!(json["flake_source"]["repo"], "tool");
+        assert_eq!(json["package_longDescription"], "A tool that does things");
+        assert!(json.get("found_in").is_none());
+    }
+
+    #[test]
+    fn test_found_in_round_trip() {
+        let package = NixPackage {
+            package_attr_name: "ripgrep".to_owned(),
+            found_in: Some(Channel::Stable {
+                year: 23,
+                month: 11,
+            }),
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&package).unwrap();
+        assert_eq!(json["found_in"], "23.11");
+        assert!(!serde_json::from_value::<NixPackage>(json.clone())
+            .unwrap()
+            .extra
+            .contains_key("found_in"));
+        assert_eq!(serde_json::from_value::<NixPackage>(json).unwrap(), package);
+    }
+
+    #[test]
+    fn test_legacy_total() {
+        let body = r#"{ "hits": { "total": 3, "hits": [] } }"#;
+        let results = parse_search_response::<NixPackage>(200, body.to_owned()).unwrap();
+        assert_eq!(results.total.value, 3);
+        assert_eq!(results.total.relation, TotalHitsRelation::Equal);
+    }
 }
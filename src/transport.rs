@@ -0,0 +1,188 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use url::Url;
+
+use crate::NixSearchError;
+
+/// the http method of a [`TransportRequest`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Method {
+    Get,
+    Post,
+}
+
+/// Everything a [`Transport`] needs to make a request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransportRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<serde_json::Value>,
+}
+
+impl TransportRequest {
+    /// the value of the first header called `name` (ignoring case)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// What came back from a [`TransportRequest`].
+///
+/// Error statuses are *not* errors at this level, elastic search
+/// explains what went wrong in the body, so the body is always kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+/// Sends requests on behalf of a [`Query`](crate::Query).
+///
+/// [`UreqTransport`] is what a [`Client`](crate::Client) uses unless told otherwise,
+/// [`MemoryTransport`] answers with canned responses so that code
+/// depending on this crate can be tested without a network.
+pub trait Transport {
+    fn send(&self, request: &TransportRequest) -> Result<TransportResponse, NixSearchError>;
+}
+
+impl<T: Transport + ?Sized> Transport for &T {
+    fn send(&self, request: &TransportRequest) -> Result<TransportResponse, NixSearchError> {
+        (**self).send(request)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send(&self, request: &TransportRequest) -> Result<TransportResponse, NixSearchError> {
+        (**self).send(request)
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn send(&self, request: &TransportRequest) -> Result<TransportResponse, NixSearchError> {
+        (**self).send(request)
+    }
+}
+
+/// The default [`Transport`], a blocking [`ureq::Agent`].
+#[derive(Debug, Clone)]
+pub struct UreqTransport {
+    agent: ureq::Agent,
+}
+
+impl UreqTransport {
+    pub fn new(agent: ureq::Agent) -> Self {
+        Self { agent }
+    }
+}
+
+impl Default for UreqTransport {
+    fn default() -> Self {
+        Self::new(ureq::Agent::new())
+    }
+}
+
+impl Transport for UreqTransport {
+    fn send(&self, request: &TransportRequest) -> Result<TransportResponse, NixSearchError> {
+        let method = match request.method {
+            Method::Get => "GET",
+            Method::Post => "POST",
+        };
+
+        let mut req = self.agent.request_url(method, &request.url);
+        for (key, value) in &request.headers {
+            req = req.set(key, value);
+        }
+
+        let res = match &request.body {
+            Some(body) => req.send_string(&body.to_string()),
+            None => req.call(),
+        };
+
+        let res = match res {
+            Ok(res) => res,
+            Err(ureq::Error::Status(_, res)) => res,
+            Err(ureq::Error::Transport(transport)) if is_timeout(&transport) => {
+                return Err(NixSearchError::Timeout { error: None })
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let status = res.status();
+        let body = res
+            .into_string()
+            .map_err(|source| NixSearchError::ErrorReadingSearchBody { source })?;
+
+        Ok(TransportResponse { status, body })
+    }
+}
+
+fn is_timeout(transport: &ureq::Transport) -> bool {
+    std::error::Error::source(transport)
+        .and_then(|source| source.downcast_ref::<std::io::Error>())
+        .is_some_and(|err| {
+            matches!(
+                err.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            )
+        })
+}
+
+/// An in-memory [`Transport`] for tests.
+///
+/// It answers every request with the next queued response, in order,
+/// and remembers the requests so they can be checked afterwards.
+///
+/// ```rust
+/// use nix_elastic_search::{MemoryTransport, Query};
+///
+/// let transport = MemoryTransport::new();
+/// transport.push_response(200, r#"{ "hits": { "hits": [] } }"#);
+///
+/// let query = Query::builder().channel("23.11").name("rust").build().unwrap();
+/// assert!(query.send_with(&transport).unwrap().is_empty());
+/// assert_eq!(transport.requests().len(), 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct MemoryTransport {
+    responses: Mutex<VecDeque<TransportResponse>>,
+    requests: Mutex<Vec<TransportRequest>>,
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// queue up a response for the next request
+    pub fn push_response(&self, status: u16, body: impl Into<String>) {
+        self.responses.lock().unwrap().push_back(TransportResponse {
+            status,
+            body: body.into(),
+        });
+    }
+
+    /// every request sent so far, oldest first
+    pub fn requests(&self) -> Vec<TransportRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn send(&self, request: &TransportRequest) -> Result<TransportResponse, NixSearchError> {
+        self.requests.lock().unwrap().push(request.clone());
+        self.responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| NixSearchError::TransportError {
+                source: format!("no response queued for {}", request.url).into(),
+            })
+    }
+}
@@ -1,8 +1,12 @@
+use std::{fmt, str::FromStr};
+
 use scraper::Selector;
 use serde::{Deserialize, Serialize};
 use url::Url;
 
-use crate::NixSearchError;
+use crate::{
+    Channel, Client, Date, Method, NixSearchError, Transport, TransportRequest, TransportResponse,
+};
 
 /// Represents verbose version information
 /// for a chosen package.
@@ -10,7 +14,65 @@ use crate::NixSearchError;
 pub struct PackageVersion {
     pub version: String,
     pub revision: String,
-    pub date: String,
+    /// the day of the revision
+    pub date: Date,
+    /// the date as the source wrote it, nixhub's has the time too, like `2024-01-09T04:30:00Z`
+    pub date_raw: String,
+}
+
+/// A channel the version history is looked up in, lazamar keeps one per channel.
+///
+/// ```rust
+/// use nix_elastic_search::{Channel, VersionChannel};
+///
+/// let stable: VersionChannel = "nixos-23.11".parse().unwrap();
+/// assert_eq!(stable, VersionChannel::NixOS(Channel::Stable { year: 23, month: 11 }));
+/// assert_eq!(VersionChannel::default().to_string(), "nixpkgs-unstable");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum VersionChannel {
+    /// `nixpkgs-unstable`
+    #[default]
+    NixpkgsUnstable,
+    /// `nixpkgs-darwin`
+    NixpkgsDarwin,
+    /// a NixOS channel, like `nixos-23.11` or `nixos-unstable`
+    NixOS(Channel),
+}
+
+impl fmt::Display for VersionChannel {
+    /// the channel name lazamar takes
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VersionChannel::NixpkgsUnstable => f.write_str("nixpkgs-unstable"),
+            VersionChannel::NixpkgsDarwin => f.write_str("nixpkgs-darwin"),
+            VersionChannel::NixOS(channel) => f.write_str(&channel.full_name()),
+        }
+    }
+}
+
+impl FromStr for VersionChannel {
+    type Err = NixSearchError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nixpkgs-unstable" => Ok(VersionChannel::NixpkgsUnstable),
+            "nixpkgs-darwin" => Ok(VersionChannel::NixpkgsDarwin),
+            // only the full name, `unstable` alone would be ambiguous here.
+            _ if s.starts_with("nixos-") => Ok(VersionChannel::NixOS(s.parse()?)),
+            _ => Err(NixSearchError::InvalidChannel {
+                channel: s.to_owned(),
+            }),
+        }
+    }
+}
+
+/// The history of a channel search.nixos.org searches, its indices are built
+/// from the NixOS channels, so `unstable` is `nixos-unstable`.
+impl From<Channel> for VersionChannel {
+    fn from(value: Channel) -> Self {
+        VersionChannel::NixOS(value)
+    }
 }
 
 /// Can lookup a specific package's old versions.
@@ -25,50 +87,369 @@ pub struct PackageVersion {
 ///
 /// that said, unintuitively, you should call this function with
 /// package_pname, anything else won't yield results.
+///
+/// The revisions are from `channel`, a machine tracking a stable
+/// channel wants that channel's revisions, not unstable's.
 pub fn lookup_package_versions(
     package_name: &str,
+    channel: VersionChannel,
+) -> Result<Vec<PackageVersion>, crate::NixSearchError> {
+    Client::shared().lookup_package_versions(package_name, channel)
+}
+
+impl Client {
+    /// [`lookup_package_versions`], through this client's transport,
+    /// so a client with a [`Cache`](crate::Cache) only asks once a day.
+    ///
+    /// THIS IS STILL A WEB SCRAPER: USE RESPONSIBLY.
+    pub fn lookup_package_versions(
+        &self,
+        package_name: &str,
+        channel: VersionChannel,
+    ) -> Result<Vec<PackageVersion>, NixSearchError> {
+        self.lookup_package_versions_with(&*self.transport, package_name, channel)
+    }
+
+    pub(crate) fn lookup_package_versions_with(
+        &self,
+        transport: &dyn Transport,
+        package_name: &str,
+        channel: VersionChannel,
+    ) -> Result<Vec<PackageVersion>, NixSearchError> {
+        let url = versions_url(package_name, channel)?;
+        let body = self.get_versions_page(transport, package_name, url)?;
+        parse_versions_page(package_name, &body)
+    }
+
+    /// GET a page of version history, the body when it's a success
+    pub(crate) fn get_versions_page(
+        &self,
+        transport: &dyn Transport,
+        package_name: &str,
+        url: Url,
+    ) -> Result<String, NixSearchError> {
+        let res = transport.send(&self.versions_request(url))?;
+        versions_page_body(package_name, res)
+    }
+
+    /// [`Client::lookup_package_versions`], without blocking, see [`lookup_package_versions_async`].
+    #[cfg(feature = "async")]
+    pub async fn lookup_package_versions_async(
+        &self,
+        package_name: &str,
+        channel: VersionChannel,
+    ) -> Result<Vec<PackageVersion>, NixSearchError> {
+        let request = self.versions_request(versions_url(package_name, channel)?);
+        let res = self.send_request_async(&request).await?;
+        let body = versions_page_body(package_name, res)?;
+        parse_versions_page(package_name, &body)
+    }
+
+    /// a GET of a version history page
+    fn versions_request(&self, url: Url) -> TransportRequest {
+        // not `Client::request`, the credentials are for elastic search.
+        TransportRequest {
+            method: Method::Get,
+            url,
+            headers: vec![("User-Agent".to_owned(), self.user_agent().to_owned())],
+            body: None,
+        }
+    }
+}
+
+/// the body of a version history page, when it's a success
+fn versions_page_body(
+    package_name: &str,
+    res: TransportResponse,
+) -> Result<String, NixSearchError> {
+    match res.status {
+        200..=299 => Ok(res.body),
+        404 => Err(NixSearchError::PackageVersionsNotFound {
+            package_name: package_name.to_owned(),
+        }),
+        status => Err(NixSearchError::TransportError {
+            source: format!("the version search for `{package_name}` failed with status {status}")
+                .into(),
+        }),
+    }
+}
+
+/// The same as [`lookup_package_versions`], but without blocking.
+///
+/// It fails with the same [`NixSearchError`] variants, only connection failures
+/// come back as [`NixSearchError::ReqwestError`] instead of [`NixSearchError::UreqError`].
+///
+/// THIS IS STILL A WEB SCRAPER: USE RESPONSIBLY.
+#[cfg(feature = "async")]
+pub async fn lookup_package_versions_async(
+    package_name: &str,
+    channel: VersionChannel,
 ) -> Result<Vec<PackageVersion>, crate::NixSearchError> {
-    let url = Url::parse_with_params(
+    Client::shared()
+        .lookup_package_versions_async(package_name, channel)
+        .await
+}
+
+fn versions_url(package_name: &str, channel: VersionChannel) -> Result<Url, NixSearchError> {
+    Url::parse_with_params(
         "https://lazamar.co.uk/nix-versions/",
-        [("channel", "nixpkgs-unstable"), ("package", package_name)],
+        [
+            ("channel", channel.to_string().as_str()),
+            ("package", package_name),
+        ],
     )
     .map_err(|e| NixSearchError::InvalidPackageNameError {
         package_name: package_name.to_owned(),
         source: e,
-    })?;
-
-    let site = ureq::get(&url.to_string()).call()?;
+    })
+}
 
-    let site_text = site
-        .into_string()
-        .map_err(|e| NixSearchError::ErrorReadingVersionBody {
-            package_name: package_name.to_owned(),
-            source: e,
-        })?;
+/// The columns of the versions table, found by their header so
+/// whitespace or a reordered table doesn't mix them up.
+struct Columns {
+    version: usize,
+    revision: usize,
+    date: usize,
+}
 
-    let parsed = scraper::Html::parse_document(&site_text);
+impl Columns {
+    fn from_header(header: &[String]) -> Option<Self> {
+        let find = |name: &str| header.iter().position(|h| h.eq_ignore_ascii_case(name));
+        Some(Columns {
+            version: find("version")?,
+            revision: find("revision")?,
+            date: find("date")?,
+        })
+    }
+}
 
-    let select = Selector::parse("html > body > section > table > tbody").unwrap();
-    let mut parse = parsed.select(&select);
+/// the trimmed text of every `th` and `td` directly in `row`
+fn cells(row: scraper::ElementRef<'_>) -> Vec<String> {
+    row.children()
+        .filter_map(scraper::ElementRef::wrap)
+        .filter(|cell| matches!(cell.value().name(), "th" | "td"))
+        .map(|cell| cell.text().collect::<String>().trim().to_owned())
+        .collect()
+}
 
-    let element = parse
-        .next()
-        .ok_or(NixSearchError::MissingTableForVersions)?;
+fn parse_versions_page(
+    package_name: &str,
+    site_text: &str,
+) -> Result<Vec<PackageVersion>, NixSearchError> {
+    let parsed = scraper::Html::parse_document(site_text);
+    let table_selector = Selector::parse("table").unwrap();
     let row_selector = Selector::parse("tr").unwrap();
+    let header_selector = Selector::parse("th").unwrap();
 
-    let mut versions = Vec::new();
-    for row in element.select(&row_selector) {
-        let version = row.text().nth(1).map(ToOwned::to_owned);
-        let revision = row.text().nth(2).map(ToOwned::to_owned);
-        let date = row.text().nth(3).map(ToOwned::to_owned);
-
-        if let Some(((version, revision), date)) = version.zip(revision).zip(date) {
-            versions.push(PackageVersion {
-                version,
-                revision,
-                date,
+    let changed = |reason: String| NixSearchError::VersionsPageChanged { reason };
+
+    // the first table with a version, revision and date column
+    let table = parsed.select(&table_selector).find_map(|table| {
+        let header = table
+            .select(&row_selector)
+            .find(|row| row.select(&header_selector).next().is_some())?;
+        Some((table, Columns::from_header(&cells(header))?))
+    });
+
+    let Some((table, columns)) = table else {
+        if says_not_found(&parsed, package_name) {
+            return Err(NixSearchError::PackageVersionsNotFound {
+                package_name: package_name.to_owned(),
             });
         }
+        return Err(changed(
+            "there is no table with a version, revision and date column".to_owned(),
+        ));
+    };
+
+    let mut versions = Vec::new();
+    for row in table.select(&row_selector) {
+        let cells = cells(row);
+        if row.select(&header_selector).next().is_some() || cells.is_empty() {
+            continue;
+        }
+
+        let cell = |column: usize| {
+            cells.get(column).cloned().ok_or_else(|| {
+                changed(format!(
+                    "a row has {} cells, expected at least {}",
+                    cells.len(),
+                    column + 1
+                ))
+            })
+        };
+        let version = cell(columns.version)?;
+        let revision = cell(columns.revision)?;
+        let date = cell(columns.date)?;
+
+        if version.is_empty() {
+            return Err(changed("a row has no version".to_owned()));
+        }
+        if !is_commit_hash(&revision) {
+            return Err(changed(format!("`{revision}` is not a commit hash")));
+        }
+        let Ok(parsed) = date.parse() else {
+            return Err(changed(format!("`{date}` is not a date like 2024-01-31")));
+        };
+
+        versions.push(PackageVersion {
+            version,
+            revision,
+            date: parsed,
+            date_raw: date,
+        });
+    }
+
+    if versions.is_empty() {
+        return Err(NixSearchError::PackageVersionsNotFound {
+            package_name: package_name.to_owned(),
+        });
     }
     Ok(versions)
 }
+
+/// Whether the page has the message the version search shows instead of the table,
+/// `<p>No results found for <b>{package}</b> in {channel}.</p>`.
+///
+/// Only the element's own text counts, so prose elsewhere
+/// mentioning "not found" doesn't look like a missing package.
+fn says_not_found(parsed: &scraper::Html, package_name: &str) -> bool {
+    let paragraph_selector = Selector::parse("p").unwrap();
+    parsed.select(&paragraph_selector).any(|paragraph| {
+        let own_text = paragraph
+            .children()
+            .filter_map(|child| child.value().as_text())
+            .map(|text| &**text)
+            .collect::<String>();
+        let package = paragraph
+            .children()
+            .filter_map(scraper::ElementRef::wrap)
+            .find(|child| child.value().name() == "b")
+            .map(|b| b.text().collect::<String>());
+
+        own_text.trim_start().starts_with("No results found for")
+            && package.is_some_and(|package| package.trim() == package_name)
+    })
+}
+
+/// a full, 40 character, git commit hash
+pub(crate) fn is_commit_hash(revision: &str) -> bool {
+    revision.len() == 40 && revision.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MemoryTransport;
+
+    /// pages saved from the version search (and made up
+    /// ones for the layouts it could change to)
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_str!(concat!("../tests/fixtures/lazamar/", $name, ".html"))
+        };
+    }
+
+    #[test]
+    fn test_lookup_through_transport() {
+        let transport = MemoryTransport::new();
+        transport.push_response(200, fixture!("ripgrep"));
+        transport.push_response(503, "down for maintenance");
+
+        let client = Client::default();
+        let stable = VersionChannel::NixOS(Channel::Stable {
+            year: 23,
+            month: 11,
+        });
+        let versions = client
+            .lookup_package_versions_with(&transport, "ripgrep", stable)
+            .unwrap();
+        assert_eq!(versions[0].version, "14.1.0");
+        assert_eq!(versions[0].date.to_string(), "2024-01-09");
+
+        let request = &transport.requests()[0];
+        assert_eq!(request.method, Method::Get);
+        assert_eq!(request.url.host_str(), Some("lazamar.co.uk"));
+        assert_eq!(
+            request.url.query(),
+            Some("channel=nixos-23.11&package=ripgrep")
+        );
+        assert!(request.header("authorization").is_none());
+
+        assert!(client
+            .lookup_package_versions_with(&transport, "ripgrep", stable)
+            .is_err());
+    }
+
+    #[test]
+    fn test_version_channel() {
+        for name in [
+            "nixpkgs-unstable",
+            "nixpkgs-darwin",
+            "nixos-unstable",
+            "nixos-23.05",
+        ] {
+            assert_eq!(name.parse::<VersionChannel>().unwrap().to_string(), name);
+        }
+        assert_eq!(
+            VersionChannel::from(Channel::Unstable).to_string(),
+            "nixos-unstable"
+        );
+        assert!("unstable".parse::<VersionChannel>().is_err());
+        assert!("nixos-23.13".parse::<VersionChannel>().is_err());
+    }
+
+    #[test]
+    fn test_parse_versions_page() {
+        let versions = parse_versions_page("ripgrep", fixture!("ripgrep")).unwrap();
+        assert_eq!(
+            versions
+                .iter()
+                .map(|v| (v.version.as_str(), v.date.to_string()))
+                .collect::<Vec<_>>(),
+            [
+                ("14.1.0", "2024-01-09".to_owned()),
+                ("14.0.3", "2023-12-02".to_owned()),
+                ("13.0.0", "2021-06-24".to_owned())
+            ]
+        );
+        assert_eq!(
+            versions[0].revision,
+            "9957cd48326fe8dbd52fdc50dd2502307f188b0d"
+        );
+
+        // the columns are found by their header, not their place
+        let reordered = parse_versions_page("ripgrep", fixture!("reordered")).unwrap();
+        assert_eq!(
+            reordered,
+            [PackageVersion {
+                version: "14.1.0".to_owned(),
+                revision: "9957cd48326fe8dbd52fdc50dd2502307f188b0d".to_owned(),
+                date: "2024-02-29".parse().unwrap(),
+                date_raw: "2024-02-29".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_versions_page_errors() {
+        assert!(matches!(
+            parse_versions_page("rip-grep", fixture!("not_found")),
+            Err(NixSearchError::PackageVersionsNotFound { package_name }) if package_name == "rip-grep"
+        ));
+        assert!(matches!(
+            parse_versions_page("ripgrep", fixture!("changed_layout")),
+            Err(NixSearchError::VersionsPageChanged { .. })
+        ));
+        // a new layout with "not found" and "no results" in its prose is still a new layout
+        assert!(matches!(
+            parse_versions_page("ripgrep", fixture!("changed_layout_prose")),
+            Err(NixSearchError::VersionsPageChanged { .. })
+        ));
+        // swapped columns would put a date where the revision goes
+        assert!(matches!(
+            parse_versions_page("ripgrep", fixture!("bad_revision")),
+            Err(NixSearchError::VersionsPageChanged { reason }) if reason.contains("commit hash")
+        ));
+    }
+}
@@ -0,0 +1,342 @@
+use std::{fmt, str::FromStr};
+
+use serde_json::json;
+
+use crate::{NixPackage, NixSearchError, NixVersion, SearchHit};
+
+/// A version requirement like `>=1.70, <2`, `~3.11` or `=14.*`.
+///
+/// Every comma separated comparator has to match, versions
+/// are compared with [`NixVersion`]'s ordering:
+///
+/// - `>=`, `>`, `<=`, `<` and `=` compare the whole version, a bare version is `=`
+/// - `~3.11` is `3.11` or anything after it that starts with `3.11`, like `3.11.4`
+/// - `=14.*` (or `14.*`) is anything whose components start with `14`
+///
+/// Unlike [`MatchVersion`](crate::MatchVersion), `1.2` doesn't match `1.20`:
+///
+/// ```rust
+/// use nix_elastic_search::{NixVersion, VersionReq};
+///
+/// let req: VersionReq = ">=1.70, <2".parse().unwrap();
+/// assert!(req.matches(&NixVersion::new("1.75.0")));
+/// assert!(!req.matches(&NixVersion::new("1.9")));
+///
+/// let req: VersionReq = "=1.2.*".parse().unwrap();
+/// assert!(req.matches(&NixVersion::new("1.2.3")));
+/// assert!(!req.matches(&NixVersion::new("1.20")));
+/// ```
+///
+/// As a [`Clause`](crate::Clause) the exact and prefix comparators narrow the search
+/// on the server, the rest (and the exact check) can only happen once the hits
+/// are back, see [`QueryBuilder::version_req`](crate::QueryBuilder::version_req).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    comparators: Vec<Comparator>,
+}
+
+/// One comparator of a [`VersionReq`], like `>=1.70`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparator {
+    pub op: Op,
+    pub version: NixVersion,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    /// `=1.2`, or just `1.2`
+    Exact,
+    /// `>1.2`
+    Greater,
+    /// `>=1.2`
+    GreaterEq,
+    /// `<1.2`
+    Less,
+    /// `<=1.2`
+    LessEq,
+    /// `~1.2`, at least `1.2` and starting with `1.2`
+    Tilde,
+    /// `=1.2.*`, starting with `1.2`
+    Wildcard,
+}
+
+impl VersionReq {
+    /// matches every version, what `*` parses to
+    pub const STAR: VersionReq = VersionReq {
+        comparators: Vec::new(),
+    };
+
+    pub fn comparators(&self) -> &[Comparator] {
+        &self.comparators
+    }
+
+    /// whether `version` meets every comparator
+    pub fn matches(&self, version: &NixVersion) -> bool {
+        self.comparators
+            .iter()
+            .all(|comparator| comparator.matches(version))
+    }
+
+    /// whether [`NixPackage::package_pversion`] meets every comparator
+    pub fn matches_package(&self, package: &NixPackage) -> bool {
+        self.matches(&package.nix_version())
+    }
+
+    /// Keep the versions that meet the requirement, like the
+    /// ones [`lookup_package_versions`](crate::lookup_package_versions) found.
+    ///
+    /// ```rust
+    /// use nix_elastic_search::{PackageVersion, VersionReq};
+    ///
+    /// let version = |version: &str| PackageVersion {
+    ///     version: version.to_owned(),
+    ///     revision: "0".repeat(40),
+    ///     date: "2024-01-01".parse().unwrap(),
+    ///     date_raw: "2024-01-01".to_owned(),
+    /// };
+    /// let versions = vec![version("14.1.0"), version("13.0.0"), version("140")];
+    ///
+    /// let req: VersionReq = "=14.*".parse().unwrap();
+    /// assert_eq!(req.filter_versions(versions), vec![version("14.1.0")]);
+    /// ```
+    #[cfg(feature = "version-search")]
+    pub fn filter_versions(
+        &self,
+        versions: Vec<crate::PackageVersion>,
+    ) -> Vec<crate::PackageVersion> {
+        versions
+            .into_iter()
+            .filter(|version| self.matches(&version.nix_version()))
+            .collect()
+    }
+
+    /// Narrow `package_pversion` as far as elastic search can, the exact and prefix
+    /// comparators. The ordering ones always match here, they're checked on the hits.
+    ///
+    /// Anything matched here is only a candidate, [`VersionReq::matches`] has the final say.
+    pub fn to_json(&self) -> serde_json::Value {
+        let narrowing = self
+            .comparators
+            .iter()
+            .filter_map(Comparator::to_json)
+            .collect::<Vec<_>>();
+
+        if narrowing.is_empty() {
+            return json!({ "match_all": {} });
+        }
+        json!({ "bool": { "filter": narrowing } })
+    }
+
+    /// drop the hits that don't meet the requirement
+    pub(crate) fn retain(&self, hits: &mut Vec<SearchHit<NixPackage>>) {
+        hits.retain(|hit| self.matches_package(&hit.source));
+    }
+}
+
+impl Comparator {
+    pub fn matches(&self, version: &NixVersion) -> bool {
+        let starts_with = || {
+            let mut components = version.components();
+            self.version
+                .components()
+                .all(|prefix| components.next().is_some_and(|c| same_component(c, prefix)))
+        };
+
+        match self.op {
+            Op::Exact => *version == self.version,
+            Op::Greater => *version > self.version,
+            Op::GreaterEq => *version >= self.version,
+            Op::Less => *version < self.version,
+            Op::LessEq => *version <= self.version,
+            Op::Tilde => *version >= self.version && starts_with(),
+            Op::Wildcard => starts_with(),
+        }
+    }
+
+    fn to_json(&self) -> Option<serde_json::Value> {
+        let version = self.version.as_str();
+        match self.op {
+            // the version as written, `=1.2` doesn't find `1.02` on the server
+            Op::Exact => Some(json!({ "term": { "package_pversion": version } })),
+            // a prefix of the text is looser than a prefix of the
+            // components (`1.2` finds `1.20`), the hits are checked after.
+            Op::Tilde | Op::Wildcard => Some(json!({ "prefix": { "package_pversion": version } })),
+            _ => None,
+        }
+    }
+}
+
+/// components are equal the way [`NixVersion`] compares them, so `07` is `7`
+fn same_component(left: &str, right: &str) -> bool {
+    NixVersion::new(left) == NixVersion::new(right)
+}
+
+impl FromStr for VersionReq {
+    type Err = NixSearchError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| NixSearchError::InvalidVersionReq {
+            req: s.to_owned(),
+            reason: reason.to_owned(),
+        };
+
+        if s.trim() == "*" {
+            return Ok(VersionReq::STAR);
+        }
+
+        let comparators = s
+            .split(',')
+            .map(|part| Comparator::parse(part.trim()).map_err(|reason| invalid(&reason)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(VersionReq { comparators })
+    }
+}
+
+impl Comparator {
+    /// the error is why `s` isn't a comparator, [`VersionReq`] turns it into a [`NixSearchError`]
+    fn parse(s: &str) -> Result<Self, String> {
+        // longest operators first, so `>=` isn't read as `>`
+        let (op, rest) = [
+            (">=", Op::GreaterEq),
+            ("<=", Op::LessEq),
+            ("==", Op::Exact),
+            (">", Op::Greater),
+            ("<", Op::Less),
+            ("=", Op::Exact),
+            ("~", Op::Tilde),
+        ]
+        .into_iter()
+        .find_map(|(prefix, op)| Some((op, s.strip_prefix(prefix)?)))
+        .unwrap_or((Op::Exact, s));
+        let rest = rest.trim();
+
+        let (op, version) = match rest.strip_suffix(".*") {
+            Some(prefix) if op == Op::Exact => (Op::Wildcard, prefix),
+            Some(_) => return Err(format!("`{s}`: only `=` takes a wildcard")),
+            None => (op, rest),
+        };
+
+        if version.is_empty() {
+            return Err(format!("`{s}` has no version"));
+        }
+        if version.contains(|c: char| c.is_whitespace() || c == '*') {
+            return Err(format!("`{s}` is not a version"));
+        }
+
+        Ok(Comparator {
+            op,
+            version: NixVersion::new(version),
+        })
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.comparators.is_empty() {
+            return f.write_str("*");
+        }
+        for (i, comparator) in self.comparators.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{comparator}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = &self.version;
+        match self.op {
+            Op::Exact => write!(f, "={version}"),
+            Op::Greater => write!(f, ">{version}"),
+            Op::GreaterEq => write!(f, ">={version}"),
+            Op::Less => write!(f, "<{version}"),
+            Op::LessEq => write!(f, "<={version}"),
+            Op::Tilde => write!(f, "~{version}"),
+            Op::Wildcard => write!(f, "={version}.*"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn req(req: &str) -> VersionReq {
+        req.parse().unwrap()
+    }
+
+    fn matches(req_str: &str, version: &str) -> bool {
+        req(req_str).matches(&NixVersion::new(version))
+    }
+
+    #[test]
+    fn test_version_req_matches() {
+        assert!(matches(">=1.70, <2", "1.70"));
+        assert!(matches(">=1.70, <2", "1.75.0"));
+        assert!(!matches(">=1.70, <2", "1.9"));
+        assert!(!matches(">=1.70, <2", "2.0"));
+        assert!(!matches(">=1.70, <2", "2"));
+
+        assert!(matches("~3.11", "3.11"));
+        assert!(matches("~3.11", "3.11.7"));
+        assert!(!matches("~3.11", "3.11pre1"));
+        assert!(!matches("~3.11", "3.110"));
+        assert!(!matches("~3.11", "3.12"));
+
+        assert!(matches("=14.*", "14"));
+        assert!(matches("14.*", "14.1.0"));
+        assert!(!matches("=14.*", "140"));
+        assert!(!matches("=14.*", "1.4"));
+
+        assert!(matches("1.2", "1.02"));
+        assert!(!matches("=1.2", "1.20"));
+        assert!(matches("*", "anything"));
+    }
+
+    #[test]
+    fn test_version_req_parse() {
+        assert_eq!(
+            req(">= 1.70 ,<2").comparators(),
+            [
+                Comparator {
+                    op: Op::GreaterEq,
+                    version: NixVersion::new("1.70")
+                },
+                Comparator {
+                    op: Op::Less,
+                    version: NixVersion::new("2")
+                },
+            ]
+        );
+        assert_eq!(req(">=1.70, <2").to_string(), ">=1.70, <2");
+        assert_eq!(req("14.*").to_string(), "=14.*");
+        assert_eq!(req("*"), VersionReq::STAR);
+
+        for bad in ["", ">=", "1.0,", ">=1.*", "1.* 2", "~*"] {
+            assert!(
+                matches!(
+                    bad.parse::<VersionReq>(),
+                    Err(NixSearchError::InvalidVersionReq { .. })
+                ),
+                "{bad:?} parsed"
+            );
+        }
+    }
+
+    #[test]
+    fn test_version_req_to_json() {
+        assert_eq!(
+            req("=14.*, <14.2").to_json(),
+            json!({ "bool": { "filter": [{ "prefix": { "package_pversion": "14" } }] } })
+        );
+        assert_eq!(
+            req("=1.2").to_json(),
+            json!({ "bool": { "filter": [{ "term": { "package_pversion": "1.2" } }] } })
+        );
+        assert_eq!(req(">=1.70").to_json(), json!({ "match_all": {} }));
+    }
+}
@@ -0,0 +1,874 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    version::is_commit_hash, Channel, Client, NixPackage, NixSearchError, PackageVersion,
+    VersionChannel,
+};
+
+/// Where the version history of a package comes from.
+///
+/// [`Lazamar`] scrapes the site [`lookup_package_versions`](crate::lookup_package_versions)
+/// always used, [`Nixhub`] asks a JSON api. Anything else, like a mirror of
+/// your own, only has to implement this to work with [`NixPackage::all_versions`].
+pub trait VersionSource {
+    /// Every version of `package` this source knows, with the
+    /// nixpkgs revision it came from, newest first.
+    ///
+    /// Not every source has every channel: [`Nixhub`] fails with
+    /// [`NixSearchError::UnsupportedChannel`] for anything but unstable,
+    /// [`NixpkgsGit`] has whichever branch is checked out.
+    fn package_versions(
+        &self,
+        package: &NixPackage,
+        channel: VersionChannel,
+    ) -> Result<Vec<PackageVersion>, NixSearchError>;
+}
+
+impl<T: VersionSource + ?Sized> VersionSource for &T {
+    fn package_versions(
+        &self,
+        package: &NixPackage,
+        channel: VersionChannel,
+    ) -> Result<Vec<PackageVersion>, NixSearchError> {
+        (**self).package_versions(package, channel)
+    }
+}
+
+impl<T: VersionSource + ?Sized> VersionSource for Box<T> {
+    fn package_versions(
+        &self,
+        package: &NixPackage,
+        channel: VersionChannel,
+    ) -> Result<Vec<PackageVersion>, NixSearchError> {
+        (**self).package_versions(package, channel)
+    }
+}
+
+/// The version history scraped from <https://lazamar.co.uk/nix-versions>,
+/// by `package_pname`, see [`lookup_package_versions`](crate::lookup_package_versions).
+///
+/// THIS IS A WEB SCRAPER: USE RESPONSIBLY.
+#[derive(Debug, Clone)]
+pub struct Lazamar {
+    client: Client,
+}
+
+impl Lazamar {
+    /// scrape through `client`'s transport, and so its cache
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Default for Lazamar {
+    fn default() -> Self {
+        Self::new(Client::shared().clone())
+    }
+}
+
+impl VersionSource for Lazamar {
+    fn package_versions(
+        &self,
+        package: &NixPackage,
+        channel: VersionChannel,
+    ) -> Result<Vec<PackageVersion>, NixSearchError> {
+        self.client
+            .lookup_package_versions(&package.package_pname, channel)
+    }
+}
+
+/// The version history from a nixhub style JSON api, like the
+/// one behind <https://www.nixhub.io>, by `package_pname`.
+///
+/// Asks `<base url>info?name=<pname>` and expects the releases back:
+///
+/// ```json
+/// { "name": "ripgrep", "releases": [
+///     { "version": "14.1.0", "last_updated": "2024-01-09T04:30:00Z",
+///       "platforms": [{ "attribute_path": "ripgrep", "commit_hash": "9957cd4..." }] }
+/// ] }
+/// ```
+///
+/// There are no channels, every revision is from `nixpkgs-unstable`, so
+/// asking for a stable channel fails with [`NixSearchError::UnsupportedChannel`].
+#[derive(Debug, Clone)]
+pub struct Nixhub {
+    client: Client,
+    base_url: Url,
+}
+
+impl Nixhub {
+    pub const DEFAULT_BASE_URL: &'static str = "https://search.devbox.sh/v1/";
+
+    /// ask the default api through `client`'s transport, and so its cache
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            base_url: Url::parse(Self::DEFAULT_BASE_URL).expect("the default url is valid"),
+        }
+    }
+
+    /// Ask another instance of the api, like a mirror. A path should
+    /// end in `/`, the same as [`ClientBuilder::base_url`](crate::ClientBuilder::base_url).
+    pub fn base_url(mut self, base_url: Url) -> Self {
+        self.base_url = base_url;
+        self
+    }
+
+    fn info_url(&self, package_name: &str) -> Result<Url, NixSearchError> {
+        let mut url =
+            self.base_url
+                .join("info")
+                .map_err(|e| NixSearchError::InvalidPackageNameError {
+                    package_name: package_name.to_owned(),
+                    source: e,
+                })?;
+        url.query_pairs_mut().append_pair("name", package_name);
+        Ok(url)
+    }
+}
+
+impl Default for Nixhub {
+    fn default() -> Self {
+        Self::new(Client::shared().clone())
+    }
+}
+
+#[derive(Deserialize)]
+struct NixhubInfo {
+    #[serde(default)]
+    releases: Vec<NixhubRelease>,
+}
+
+#[derive(Deserialize)]
+struct NixhubRelease {
+    version: String,
+    #[serde(default)]
+    last_updated: Option<String>,
+    #[serde(default)]
+    commit_hash: Option<String>,
+    #[serde(default)]
+    platforms: Vec<NixhubPlatform>,
+}
+
+#[derive(Deserialize)]
+struct NixhubPlatform {
+    #[serde(default)]
+    attribute_path: Option<String>,
+    #[serde(default)]
+    commit_hash: Option<String>,
+    #[serde(default)]
+    date: Option<String>,
+}
+
+impl VersionSource for Nixhub {
+    fn package_versions(
+        &self,
+        package: &NixPackage,
+        channel: VersionChannel,
+    ) -> Result<Vec<PackageVersion>, NixSearchError> {
+        if !matches!(
+            channel,
+            VersionChannel::NixpkgsUnstable | VersionChannel::NixOS(Channel::Unstable)
+        ) {
+            return Err(NixSearchError::UnsupportedChannel {
+                version_source: "nixhub".to_owned(),
+                channel: channel.to_string(),
+            });
+        }
+
+        let package_name = &package.package_pname;
+        let url = self.info_url(package_name)?;
+        let body = self
+            .client
+            .get_versions_page(&*self.client.transport, package_name, url)?;
+
+        let changed = |reason: String| NixSearchError::VersionsPageChanged { reason };
+        let info = serde_json::from_str::<NixhubInfo>(&body)
+            .map_err(|err| changed(format!("the api answered unexpected json: {err}")))?;
+
+        let mut versions = Vec::new();
+        for release in info.releases {
+            // the platform with the attribute we found, another attribute's
+            // commit isn't a pin for this package, so without one the release is skipped.
+            let platform = release
+                .platforms
+                .iter()
+                .find(|p| p.attribute_path.as_deref() == Some(&package.package_attr_name));
+            if platform.is_none() && !release.platforms.is_empty() {
+                continue;
+            }
+
+            let revision = platform
+                .and_then(|p| p.commit_hash.clone())
+                .or(release.commit_hash);
+            let date = platform
+                .and_then(|p| p.date.clone())
+                .or(release.last_updated);
+
+            let (Some(revision), Some(date)) = (revision, date) else {
+                return Err(changed(format!(
+                    "release {} has no commit hash or date",
+                    release.version
+                )));
+            };
+            if !is_commit_hash(&revision) {
+                return Err(changed(format!("`{revision}` is not a commit hash")));
+            }
+            // a timestamp, the day is what lazamar has too
+            let day = date.get(.."YYYY-MM-DD".len()).unwrap_or(&date);
+            let Ok(day) = day.parse() else {
+                return Err(changed(format!("`{date}` is not a date like 2024-01-31")));
+            };
+
+            versions.push(PackageVersion {
+                version: release.version,
+                revision,
+                date: day,
+                date_raw: date,
+            });
+        }
+
+        if versions.is_empty() {
+            return Err(NixSearchError::PackageVersionsNotFound {
+                package_name: package_name.clone(),
+            });
+        }
+        Ok(versions)
+    }
+}
+
+/// The version history from a local nixpkgs git checkout, fully offline.
+///
+/// Walks the commits that touched the file in [`NixPackage::package_position`]
+/// (following renames), and reads the `version = "...";` each of them set. A file
+/// with more than one derivation has more than one `version`, the one nearest the
+/// line of `package_position` is taken, which can still be the wrong one.
+///
+/// Every version comes with the newest commit of the walked history that still
+/// has it: the commit before the next version bump, or [`NixpkgsGit::git_ref`] for
+/// the version there. Walking a channel branch, like `nixos-23.11`, that's a revision
+/// the channel had. Walking `master` it may never have been in a channel, and so
+/// never built by hydra, a `nix shell` of it can mean building from source.
+///
+/// Needs `git` on the `PATH`. The history is walked from [`NixpkgsGit::git_ref`],
+/// `HEAD` unless set, so `channel` is ignored: check out (or point at) the branch
+/// of the channel you want, like `nixos-23.11`.
+///
+/// ```rust,no_run
+/// use nix_elastic_search::{NixpkgsGit, Query};
+///
+/// let query = Query::builder().channel("23.11").name("ripgrep").build().unwrap();
+/// let package = &query.send().unwrap()[0];
+///
+/// let mirror = NixpkgsGit::new("/srv/nixpkgs").git_ref("origin/nixos-23.11");
+/// let versions = package.all_versions(&mirror).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NixpkgsGit {
+    checkout: PathBuf,
+    git_ref: String,
+}
+
+impl NixpkgsGit {
+    /// the history of the nixpkgs checkout (or bare clone) at `checkout`
+    pub fn new(checkout: impl Into<PathBuf>) -> Self {
+        Self {
+            checkout: checkout.into(),
+            git_ref: "HEAD".to_owned(),
+        }
+    }
+
+    /// walk the history from this branch, tag or commit instead of `HEAD`
+    pub fn git_ref(mut self, git_ref: impl Into<String>) -> Self {
+        self.git_ref = git_ref.into();
+        self
+    }
+
+    pub fn checkout(&self) -> &Path {
+        &self.checkout
+    }
+
+    /// The versions set in `file`, a path relative to the root of the checkout
+    /// like `pkgs/tools/text/ripgrep/default.nix`, newest first.
+    ///
+    /// Without a line to go by, the first `version` of the file is taken.
+    pub fn file_versions(&self, file: &str) -> Result<Vec<PackageVersion>, NixSearchError> {
+        self.versions_near(file, None)
+    }
+
+    /// the versions set in `file`, by the `version` nearest `line` when there are more
+    fn versions_near(
+        &self,
+        file: &str,
+        line: Option<usize>,
+    ) -> Result<Vec<PackageVersion>, NixSearchError> {
+        if self.git_ref.starts_with('-') {
+            return Err(git_error(format!("`{}` is not a git ref", self.git_ref)));
+        }
+
+        // only the lines that changed, each commit starting
+        // with a NUL, its hash and the hashes of its parents.
+        let log = self.git(&[
+            "log",
+            "--follow",
+            "--no-color",
+            "--no-ext-diff",
+            "--patch",
+            "--unified=0",
+            "--format=%x00%H %P",
+            &self.git_ref,
+            "--",
+            file,
+        ])?;
+
+        // every version, and the newest commit that has it
+        let mut last_seen = Vec::<(&str, LastSeen)>::new();
+        // the newest commit with the version of the commit being looked at
+        let mut newest = LastSeen::Tip;
+        for commit in log.split('\0').skip(1) {
+            let (header, patch) = commit.split_once('\n').unwrap_or((commit, ""));
+            let parent = header.split_whitespace().nth(1);
+
+            let Some(version) = changed_version(patch, line) else {
+                continue;
+            };
+            // newest first, so the first commit setting a version is the newest one
+            if let Some(version) = version {
+                if !last_seen.iter().any(|(seen, _)| *seen == version) {
+                    last_seen.push((version, newest));
+                }
+            }
+            // the version before this commit is in its parent, a root commit has none
+            newest = parent.map_or(LastSeen::Nowhere, LastSeen::Commit);
+        }
+
+        let last_seen = last_seen
+            .into_iter()
+            .filter_map(|(version, seen)| match seen {
+                LastSeen::Tip => Some((version, None)),
+                LastSeen::Commit(commit) => Some((version, Some(commit))),
+                LastSeen::Nowhere => None,
+            })
+            .collect::<Vec<_>>();
+        if last_seen.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // the date of every commit a version is last seen in, the ref first
+        let mut commits = vec![self.git_ref.as_str()];
+        commits.extend(last_seen.iter().filter_map(|(_, commit)| *commit));
+        let dates = self.git(
+            &[
+                "log",
+                "--no-walk=unsorted",
+                "--date=short",
+                "--format=%H %cd",
+            ]
+            .into_iter()
+            .chain(commits)
+            .collect::<Vec<_>>(),
+        )?;
+        let dates = dates
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .collect::<Vec<_>>();
+        let (tip, _) = *dates
+            .first()
+            .ok_or_else(|| git_error(format!("`{}` has no commit", self.git_ref)))?;
+
+        last_seen
+            .into_iter()
+            .map(|(version, commit)| {
+                let revision = commit.unwrap_or(tip);
+                let (_, date) = dates
+                    .iter()
+                    .find(|(hash, _)| *hash == revision)
+                    .ok_or_else(|| git_error(format!("git didn't show commit {revision}")))?;
+                Ok(PackageVersion {
+                    version: version.to_owned(),
+                    revision: revision.to_owned(),
+                    date: date.trim().parse()?,
+                    date_raw: date.trim().to_owned(),
+                })
+            })
+            .collect()
+    }
+
+    /// run git in the checkout, its output
+    fn git(&self, args: &[&str]) -> Result<String, NixSearchError> {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(&self.checkout)
+            .args(args)
+            .output()
+            .map_err(|err| git_error(format!("couldn't run git: {err}")))?;
+
+        if !output.status.success() {
+            return Err(git_error(
+                String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// where a version is last seen, walking the history from the newest commit
+#[derive(Debug, Clone, Copy)]
+enum LastSeen<'a> {
+    /// at [`NixpkgsGit::git_ref`], it's the version there
+    Tip,
+    Commit(&'a str),
+    /// before a root commit, in no commit at all
+    Nowhere,
+}
+
+fn git_error(reason: String) -> NixSearchError {
+    NixSearchError::GitHistoryError { reason }
+}
+
+/// The file and line of a `package_position` like `pkgs/tools/text/ripgrep/default.nix:85`
+fn split_position(position: &str) -> (&str, Option<usize>) {
+    let (file, line) = match position.rsplit_once(':') {
+        Some((file, line)) if line.bytes().all(|b| b.is_ascii_digit()) => (file, line.parse().ok()),
+        _ => (position, None),
+    };
+    // positions from a store path, `/nix/store/<hash>-source/pkgs/...`
+    let file = match file.find("/pkgs/") {
+        Some(start) if file.starts_with('/') => &file[start + 1..],
+        _ => file,
+    };
+    (file, line)
+}
+
+/// What the patch of a commit did to the `version = "...";` nearest `line`
+/// (of the hunks that touched one): `None` when it didn't touch one, and inside
+/// that the version it set, `None` when it's one that can't be read, like `"${major}.1"`.
+fn changed_version(patch: &str, line: Option<usize>) -> Option<Option<&str>> {
+    // the line a hunk starts at in the new file, and its lines
+    let mut hunks = Vec::<(usize, Vec<&str>)>::new();
+    for patch_line in patch.lines() {
+        if let Some(range) = patch_line.strip_prefix("@@ ") {
+            // `@@ -12,2 +12,3 @@`
+            let start = range
+                .split_whitespace()
+                .find_map(|part| part.strip_prefix('+'))
+                .and_then(|new| new.split(',').next()?.parse().ok())
+                .unwrap_or(0);
+            hunks.push((start, Vec::new()));
+        } else if let Some((_, lines)) = hunks.last_mut() {
+            lines.push(patch_line);
+        }
+    }
+
+    let is_version = |line: &str| {
+        line.trim_start()
+            .strip_prefix("version")
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    };
+    let changes_version = |line: &&str| {
+        line.strip_prefix('+')
+            .or_else(|| line.strip_prefix('-'))
+            .is_some_and(is_version)
+    };
+    let (_, lines) = hunks
+        .iter()
+        .filter(|(_, lines)| lines.iter().any(changes_version))
+        .min_by_key(|(start, _)| line.map_or(0, |line| start.abs_diff(line)))?;
+
+    Some(
+        lines
+            .iter()
+            .filter_map(|line| line.strip_prefix('+'))
+            .find_map(version_assignment),
+    )
+}
+
+/// the version of a line like `  version = "14.1.0";`, not one built from other values
+fn version_assignment(line: &str) -> Option<&str> {
+    let value = line
+        .trim_start()
+        .strip_prefix("version")?
+        .trim_start()
+        .strip_prefix('=')?
+        .trim_start()
+        .strip_prefix('"')?;
+    let (version, rest) = value.split_once('"')?;
+
+    if version.is_empty() || version.contains("${") || !rest.trim_start().starts_with(';') {
+        return None;
+    }
+    Some(version)
+}
+
+impl VersionSource for NixpkgsGit {
+    fn package_versions(
+        &self,
+        package: &NixPackage,
+        _channel: VersionChannel,
+    ) -> Result<Vec<PackageVersion>, NixSearchError> {
+        let position =
+            package
+                .package_position
+                .as_deref()
+                .ok_or_else(|| NixSearchError::GitHistoryError {
+                    reason: format!(
+                        "`{}` has no package_position to find its file by",
+                        package.package_attr_name
+                    ),
+                })?;
+
+        let (file, line) = split_position(position);
+        let versions = self.versions_near(file, line)?;
+        if versions.is_empty() {
+            return Err(NixSearchError::PackageVersionsNotFound {
+                package_name: package.package_pname.clone(),
+            });
+        }
+        Ok(versions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{MemoryTransport, Method};
+
+    const REV: &str = "9957cd48326fe8dbd52fdc50dd2502307f188b0d";
+
+    fn package(pname: &str, attr: &str) -> NixPackage {
+        NixPackage {
+            package_pname: pname.to_owned(),
+            package_attr_name: attr.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// a nixhub that only answers from memory
+    fn nixhub() -> (Nixhub, Arc<MemoryTransport>) {
+        let transport = Arc::new(MemoryTransport::new());
+        let client = Client::builder()
+            .transport(transport.clone())
+            .build()
+            .unwrap();
+        let nixhub = Nixhub::new(client).base_url("http://localhost:8080/api/v1/".parse().unwrap());
+        (nixhub, transport)
+    }
+
+    #[test]
+    fn test_nixhub_versions() {
+        let (nixhub, transport) = nixhub();
+        transport.push_response(
+            200,
+            serde_json::json!({
+                "name": "requests",
+                "releases": [
+                    {
+                        "version": "2.31.0",
+                        "last_updated": "2024-01-09T04:30:00Z",
+                        "platforms": [
+                            { "attribute_path": "python310Packages.requests", "commit_hash": "0".repeat(40) },
+                            { "attribute_path": "python311Packages.requests", "commit_hash": REV },
+                        ],
+                    },
+                    {
+                        "version": "2.29.0",
+                        "last_updated": "2023-05-22T00:00:00Z",
+                        "platforms": [
+                            { "attribute_path": "python310Packages.requests", "commit_hash": "b".repeat(40) },
+                        ],
+                    },
+                    {
+                        "version": "2.28.1",
+                        "last_updated": "2022-09-01T00:00:00Z",
+                        "commit_hash": "a".repeat(40),
+                    },
+                ],
+            })
+            .to_string(),
+        );
+
+        let versions = nixhub
+            .package_versions(
+                &package("requests", "python311Packages.requests"),
+                VersionChannel::default(),
+            )
+            .unwrap();
+        assert_eq!(
+            versions,
+            [
+                PackageVersion {
+                    version: "2.31.0".to_owned(),
+                    revision: REV.to_owned(),
+                    date: "2024-01-09".parse().unwrap(),
+                    date_raw: "2024-01-09T04:30:00Z".to_owned(),
+                },
+                PackageVersion {
+                    version: "2.28.1".to_owned(),
+                    revision: "a".repeat(40),
+                    date: "2022-09-01".parse().unwrap(),
+                    date_raw: "2022-09-01T00:00:00Z".to_owned(),
+                },
+            ]
+        );
+
+        let request = &transport.requests()[0];
+        assert_eq!(request.method, Method::Get);
+        assert_eq!(
+            request.url.as_str(),
+            "http://localhost:8080/api/v1/info?name=requests"
+        );
+        assert!(request.header("authorization").is_none());
+    }
+
+    #[test]
+    fn test_nixhub_only_has_unstable() {
+        let (nixhub, transport) = nixhub();
+        let stable = VersionChannel::NixOS(Channel::Stable {
+            year: 23,
+            month: 11,
+        });
+
+        assert!(matches!(
+            nixhub.package_versions(&package("ripgrep", "ripgrep"), stable),
+            Err(NixSearchError::UnsupportedChannel { channel, .. }) if channel == "nixos-23.11"
+        ));
+        assert!(transport.requests().is_empty());
+
+        transport.push_response(200, r#"{ "name": "ripgrep", "releases": [] }"#);
+        assert!(matches!(
+            nixhub.package_versions(
+                &package("ripgrep", "ripgrep"),
+                VersionChannel::NixOS(Channel::Unstable)
+            ),
+            Err(NixSearchError::PackageVersionsNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_nixhub_errors() {
+        let (nixhub, transport) = nixhub();
+        let ripgrep = package("ripgrep", "ripgrep");
+        transport.push_response(404, "not found");
+        transport.push_response(200, r#"{ "name": "ripgrep", "releases": [] }"#);
+        transport.push_response(200, "<html>maintenance</html>");
+        transport.push_response(
+            200,
+            r#"{ "releases": [{ "version": "1.0", "last_updated": "2024-01-09", "commit_hash": "main" }] }"#,
+        );
+
+        let next = || nixhub.package_versions(&ripgrep, VersionChannel::default());
+        assert!(matches!(
+            next(),
+            Err(NixSearchError::PackageVersionsNotFound { .. })
+        ));
+        assert!(matches!(
+            next(),
+            Err(NixSearchError::PackageVersionsNotFound { .. })
+        ));
+        assert!(matches!(
+            next(),
+            Err(NixSearchError::VersionsPageChanged { .. })
+        ));
+        assert!(matches!(
+            next(),
+            Err(NixSearchError::VersionsPageChanged { .. })
+        ));
+    }
+
+    #[test]
+    fn test_all_versions_from_any_source() {
+        struct Mirror;
+
+        impl VersionSource for Mirror {
+            fn package_versions(
+                &self,
+                package: &NixPackage,
+                channel: VersionChannel,
+            ) -> Result<Vec<PackageVersion>, NixSearchError> {
+                Ok(vec![PackageVersion {
+                    version: format!("{} from {channel}", package.package_pname),
+                    revision: REV.to_owned(),
+                    date: "2024-01-09".parse().unwrap(),
+                    date_raw: "2024-01-09".to_owned(),
+                }])
+            }
+        }
+
+        let mut package = package("ripgrep", "ripgrep");
+        package.found_in = Some(crate::Channel::Stable {
+            year: 23,
+            month: 11,
+        });
+
+        let source: Box<dyn VersionSource> = Box::new(Mirror);
+        assert_eq!(
+            package.all_versions(&source).unwrap()[0].version,
+            "ripgrep from nixos-23.11"
+        );
+    }
+
+    #[test]
+    fn test_split_position() {
+        assert_eq!(
+            split_position("pkgs/tools/text/ripgrep/default.nix:85"),
+            ("pkgs/tools/text/ripgrep/default.nix", Some(85))
+        );
+        assert_eq!(
+            split_position("/nix/store/abc-source/pkgs/tools/text/ripgrep/default.nix:85"),
+            ("pkgs/tools/text/ripgrep/default.nix", Some(85))
+        );
+        assert_eq!(split_position("default.nix"), ("default.nix", None));
+    }
+
+    #[test]
+    fn test_changed_version() {
+        // a file with the package at line 30, and a test derivation at line 4
+        let patch = "diff --git a/default.nix b/default.nix\n\
+                     --- a/default.nix\n\
+                     +++ b/default.nix\n\
+                     @@ -4 +4 @@ let\n\
+                     -    version = \"0.1\";\n\
+                     +    version = \"0.2\";\n\
+                     @@ -31 +31 @@ buildGoModule rec {\n\
+                     -  version = \"1.4.0\";\n\
+                     +  version = \"1.5.0\";\n";
+        assert_eq!(changed_version(patch, Some(30)), Some(Some("1.5.0")));
+        assert_eq!(changed_version(patch, Some(3)), Some(Some("0.2")));
+        assert_eq!(changed_version(patch, None), Some(Some("0.2")));
+
+        let unreadable = "@@ -31 +31 @@\n-  version = \"1.4.0\";\n+  version = \"${major}.0\";\n";
+        assert_eq!(changed_version(unreadable, Some(30)), Some(None));
+
+        let description =
+            "@@ -40 +40 @@\n-  description = \"grep\";\n+  description = \"a grep\";\n";
+        assert_eq!(changed_version(description, Some(30)), None);
+        let suffix = "@@ -32 +32 @@\n+  versionSuffix = \"pre\";\n";
+        assert_eq!(changed_version(suffix, Some(30)), None);
+    }
+
+    #[test]
+    fn test_version_assignment() {
+        assert_eq!(
+            version_assignment(r#"  version = "14.1.0";"#),
+            Some("14.1.0")
+        );
+        assert_eq!(
+            version_assignment(r#"version="1.0"; # comment"#),
+            Some("1.0")
+        );
+        assert_eq!(version_assignment(r#"  version = "${major}.1";"#), None);
+        assert_eq!(version_assignment(r#"  versionSuffix = "pre";"#), None);
+        assert_eq!(version_assignment(r#"  version = "1.0" + "-rc";"#), None);
+        assert_eq!(version_assignment(r#"  src.version = "1.0";"#), None);
+    }
+
+    #[test]
+    fn test_nixpkgs_git_history() {
+        let checkout = std::env::temp_dir().join(format!(
+            "nix-elastic-search-test-nixpkgs-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&checkout);
+        let file = "pkgs/tools/text/ripgrep/default.nix";
+        std::fs::create_dir_all(checkout.join("pkgs/tools/text/ripgrep")).unwrap();
+
+        let git = |args: &[&str], date: &str| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(&checkout)
+                .args([
+                    "-c",
+                    "user.name=nixpkgs",
+                    "-c",
+                    "user.email=nixpkgs@example.org",
+                    "-c",
+                    "commit.gpgsign=false",
+                ])
+                .args(args)
+                .env("GIT_AUTHOR_DATE", format!("{date}T12:00:00Z"))
+                .env("GIT_COMMITTER_DATE", format!("{date}T12:00:00Z"))
+                .output()
+                .unwrap();
+            assert!(status.status.success(), "git {args:?}: {status:?}");
+            String::from_utf8(status.stdout).unwrap().trim().to_owned()
+        };
+        let commit = |version: &str, description: &str, date: &str| {
+            std::fs::write(
+                checkout.join(file),
+                format!(
+                    "{{ lib, rustPlatform }}:\n\nrustPlatform.buildRustPackage rec {{\n  \
+                     pname = \"ripgrep\";\n  version = \"{version}\";\n\n  \
+                     meta.description = \"{description}\";\n}}\n"
+                ),
+            )
+            .unwrap();
+            git(&["add", "."], date);
+            git(&["commit", "--quiet", "-m", "ripgrep"], date);
+            git(&["rev-parse", "HEAD"], date)
+        };
+
+        git(&["init", "--quiet"], "2021-01-01");
+        commit("13.0.0", "grep", "2021-06-24");
+        let last_13 = commit("13.0.0", "a faster grep", "2022-03-01");
+        let first_14 = commit("14.0.3", "a faster grep", "2023-12-02");
+        commit("14.1.0", "a faster grep", "2024-01-09");
+        // a commit that leaves ripgrep alone still has its version
+        std::fs::write(checkout.join("README.md"), "nixpkgs").unwrap();
+        git(&["add", "."], "2024-02-01");
+        git(&["commit", "--quiet", "-m", "readme"], "2024-02-01");
+        let tip = git(&["rev-parse", "HEAD"], "2024-02-01");
+
+        let package = NixPackage {
+            package_attr_name: "ripgrep".to_owned(),
+            package_pname: "ripgrep".to_owned(),
+            package_position: Some(format!("{file}:42")),
+            ..Default::default()
+        };
+        let versions = package.all_versions(&NixpkgsGit::new(&checkout)).unwrap();
+        let version = |version: &str, revision: &str, date: &str| PackageVersion {
+            version: version.to_owned(),
+            revision: revision.to_owned(),
+            date: date.parse().unwrap(),
+            date_raw: date.to_owned(),
+        };
+        // every version with the newest commit that still has it
+        assert_eq!(
+            versions,
+            [
+                version("14.1.0", &tip, "2024-02-01"),
+                version("14.0.3", &first_14, "2023-12-02"),
+                version("13.0.0", &last_13, "2022-03-01"),
+            ]
+        );
+
+        // from an older commit, the newer versions don't exist yet
+        let older = NixpkgsGit::new(&checkout).git_ref(first_14.clone());
+        assert_eq!(
+            older.file_versions(file).unwrap(),
+            [
+                version("14.0.3", &first_14, "2023-12-02"),
+                version("13.0.0", &last_13, "2022-03-01"),
+            ]
+        );
+
+        assert!(matches!(
+            NixpkgsGit::new(&checkout).file_versions("pkgs/missing.nix"),
+            Ok(versions) if versions.is_empty()
+        ));
+        assert!(matches!(
+            NixpkgsGit::new(checkout.join("not-a-checkout")).file_versions(file),
+            Err(NixSearchError::GitHistoryError { .. })
+        ));
+
+        std::fs::remove_dir_all(&checkout).unwrap();
+    }
+}